@@ -6,6 +6,12 @@ use std::path::Path;
 use std::io::prelude::*;
 use std::iter::{Skip, Zip};
 use std::str::Chars;
+use std::str::FromStr;
+use std::env::args;
+use std::cmp;
+
+use Rule::*;
+use Verdict::*;
 
 type PairIterator<'a, 'b> = Zip<Chars<'a>, Skip<Chars<'b>>>;
 
@@ -55,6 +61,224 @@ fn is_nice_rule2(input: &str) -> bool {
     contains_double_pair(input) && contains_letter_pair_with_space(input)
 }
 
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Rule {
+    Rule1,
+    Rule2,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Verdict {
+    Nice,
+    Naughty,
+}
+
+impl Rule {
+    fn parse(input: &str) -> Option<Self> {
+        match input {
+            "1" | "rule1" => Some(Rule1),
+            "2" | "rule2" => Some(Rule2),
+            _ => None,
+        }
+    }
+
+    fn judge(self, input: &str) -> Verdict {
+        let nice = match self {
+            Rule1 => is_nice_rule1(input),
+            Rule2 => is_nice_rule2(input),
+        };
+        if nice {
+            Nice
+        } else {
+            Naughty
+        }
+    }
+
+    fn min_nice_length(self) -> usize {
+        match self {
+            Rule1 => 3,
+            Rule2 => 4,
+        }
+    }
+}
+
+impl Verdict {
+    fn parse(input: &str) -> Option<Self> {
+        match input {
+            "nice" => Some(Nice),
+            "naughty" => Some(Naughty),
+            _ => None,
+        }
+    }
+}
+
+// xorshift64*, so that generated corpora are reproducible from a seed
+// without pulling in another dependency.
+struct Random {
+    state: u64,
+}
+
+impl Random {
+    fn new(seed: u64) -> Self {
+        // splitmix64 spreads similar seeds apart and only maps a single seed
+        // to the all-zero state xorshift can't leave.
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        Random { state: cmp::max(z ^ (z >> 31), 1) }
+    }
+
+    fn next(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn letter(&mut self) -> u8 {
+        b'a' + self.below(26) as u8
+    }
+
+    fn vowel(&mut self) -> u8 {
+        b"aeiou"[self.below(5)]
+    }
+}
+
+fn is_naughty_pair(a: u8, b: u8) -> bool {
+    matches!((a, b), (b'a', b'b') | (b'c', b'd') | (b'p', b'q') | (b'x', b'y'))
+}
+
+struct StringGenerator {
+    rule: Rule,
+    verdict: Verdict,
+    length: usize,
+    random: Random,
+}
+
+impl StringGenerator {
+    // Uniformly random strings are tried first so the corpus isn't limited to
+    // the shapes the constructions below produce.
+    const RANDOM_ATTEMPTS: usize = 16;
+
+    fn new(rule: Rule, verdict: Verdict, length: usize, seed: u64) -> Self {
+        StringGenerator {
+            rule,
+            verdict,
+            length,
+            random: Random::new(seed),
+        }
+    }
+
+    fn is_possible(&self) -> bool {
+        self.verdict == Naughty || self.length >= self.rule.min_nice_length()
+    }
+
+    fn random_string(&mut self) -> Vec<u8> {
+        let random = &mut self.random;
+        (0..self.length).map(|_| random.letter()).collect()
+    }
+
+    fn construct_nice_rule1(&mut self) -> Vec<u8> {
+        let mut chars = self.random_string();
+        let n = self.length;
+
+        let double = self.random.below(n - 1);
+        let vowel = self.random.vowel();
+        chars[double] = vowel;
+        chars[double + 1] = vowel;
+
+        let mut third = self.random.below(n - 2);
+        if third >= double {
+            third += 2;
+        }
+        chars[third] = self.random.vowel();
+
+        // The second letter of a naughty pair is never a vowel, so replacing
+        // it can't undo the vowels and the double letter placed above.
+        for i in 1..n {
+            while is_naughty_pair(chars[i - 1], chars[i]) {
+                chars[i] = self.random.letter();
+            }
+        }
+
+        chars
+    }
+
+    fn construct_nice_rule2(&mut self) -> Vec<u8> {
+        let mut chars = self.random_string();
+
+        let start = self.random.below(self.length - 3);
+        let (a, b) = (self.random.letter(), self.random.letter());
+        chars[start] = a;
+        chars[start + 1] = b;
+        chars[start + 2] = a;
+        chars[start + 3] = b;
+
+        chars
+    }
+
+    fn construct_naughty(&mut self) -> Vec<u8> {
+        // Rule 1 needs a double letter and Rule 2 needs a letter repeated with
+        // one in between, so avoiding that repetition makes the string naughty.
+        let distance = match self.rule {
+            Rule1 => 1,
+            Rule2 => 2,
+        };
+        let mut chars = Vec::with_capacity(self.length);
+        for i in 0..self.length {
+            let mut c = self.random.letter();
+            while i >= distance && chars[i - distance] == c {
+                c = self.random.letter();
+            }
+            chars.push(c);
+        }
+        chars
+    }
+
+    fn construct(&mut self) -> Vec<u8> {
+        match (self.rule, self.verdict) {
+            (Rule1, Nice) => self.construct_nice_rule1(),
+            (Rule2, Nice) => self.construct_nice_rule2(),
+            (_, Naughty) => self.construct_naughty(),
+        }
+    }
+
+    fn accept(&self, chars: Vec<u8>) -> Option<String> {
+        let string = String::from_utf8(chars).unwrap();
+        if self.rule.judge(&string) == self.verdict {
+            Some(string)
+        } else {
+            None
+        }
+    }
+}
+
+impl Iterator for StringGenerator {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        if !self.is_possible() {
+            return None;
+        }
+
+        for _ in 0..Self::RANDOM_ATTEMPTS {
+            let chars = self.random_string();
+            if let Some(string) = self.accept(chars) {
+                return Some(string);
+            }
+        }
+
+        let chars = self.construct();
+        let string = self.accept(chars);
+        assert!(string.is_some(), "Constructed string doesn't match the requested verdict");
+        string
+    }
+}
+
 fn count_how_many_strings_are_nice_rule1<'a, I>(input: I) -> usize
 where I: IntoIterator<Item = &'a String> {
     input.into_iter().filter(|l| is_nice_rule1(l)).count()
@@ -72,7 +296,37 @@ fn read_file(path: &Path) -> Vec<String> {
     input.lines().into_iter().map(|s| s.to_owned()).collect()
 }
 
+fn generate(arguments: &[String]) {
+    let usage = "Usage: day-5 generate <1|2> <nice|naughty> <length> <count> [seed]";
+    if arguments.len() < 4 {
+        println!("{}", usage);
+        return;
+    }
+
+    let rule = Rule::parse(&arguments[0]).expect("Unknown rule, expected 1 or 2");
+    let verdict = Verdict::parse(&arguments[1]).expect("Unknown verdict, expected nice or naughty");
+    let length = usize::from_str(&arguments[2]).expect("Couldn't parse the length");
+    let count = usize::from_str(&arguments[3]).expect("Couldn't parse the count");
+    let seed = arguments.get(4).map_or(0, |s| u64::from_str(s).expect("Couldn't parse the seed"));
+
+    let generator = StringGenerator::new(rule, verdict, length, seed);
+    if !generator.is_possible() {
+        println!("There are no {:?} strings of length {} for {:?}.", verdict, length, rule);
+        return;
+    }
+
+    for string in generator.take(count) {
+        println!("{}", string);
+    }
+}
+
 fn main() {
+    let arguments = args().skip(1).collect::<Vec<_>>();
+    if arguments.first().is_some_and(|a| a == "generate") {
+        generate(&arguments[1..]);
+        return;
+    }
+
     let input = read_file(Path::new("input.txt"));
 
     let mut tw = TabWriter::new(Vec::new());
@@ -153,3 +407,42 @@ fn test_is_nice_rule2() {
     assert!(!is_nice_rule2("uurcxstgmygtbstg"));
     assert!(!is_nice_rule2("ieodomkazucvgmuy"));
 }
+
+
+#[test]
+fn test_generated_strings_match_verdict() {
+    for &rule in &[Rule1, Rule2] {
+        for &verdict in &[Nice, Naughty] {
+            for length in 0..20 {
+                let generator = StringGenerator::new(rule, verdict, length, length as u64);
+                let possible = generator.is_possible();
+                let strings = generator.take(50).collect::<Vec<_>>();
+                assert_eq!(strings.len(), if possible { 50 } else { 0 });
+                for string in strings {
+                    assert_eq!(string.len(), length);
+                    assert_eq!(rule.judge(&string), verdict);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_generated_strings_are_seeded() {
+    let first = StringGenerator::new(Rule2, Nice, 16, 42).take(10).collect::<Vec<_>>();
+    let second = StringGenerator::new(Rule2, Nice, 16, 42).take(10).collect::<Vec<_>>();
+    let other = StringGenerator::new(Rule2, Nice, 16, 43).take(10).collect::<Vec<_>>();
+    assert_eq!(first, second);
+    assert!(first != other);
+}
+
+#[test]
+fn test_generated_long_strings() {
+    for &rule in &[Rule1, Rule2] {
+        for &verdict in &[Nice, Naughty] {
+            for string in StringGenerator::new(rule, verdict, 500, 7).take(5) {
+                assert_eq!(rule.judge(&string), verdict);
+            }
+        }
+    }
+}
@@ -1,89 +1,351 @@
 #[cfg(feature = "save")]
 extern crate image;
 #[cfg(feature = "save")]
-extern crate rand;
+extern crate gif;
 
 #[cfg(feature = "save")]
 use image::RgbaImage;
 #[cfg(feature = "save")]
-use rand::distributions::{IndependentSample, Range};
+use gif::{Encoder, EncodingError, Frame, Repeat};
+#[cfg(feature = "save")]
+use std::borrow::Cow;
+#[cfg(feature = "save")]
+use std::io;
 
 use std::path::Path;
 use std::fs::File;
 use std::io::prelude::*;
 use std::str::FromStr;
 use std::cmp;
+use std::fmt;
+use std::env::args;
 
 use Operation::*;
+use BoundsPolicy::*;
+use ErrorKind::*;
+use ColorMap::*;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+struct GridSize {
+    width: usize,
+    height: usize,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum BoundsPolicy {
+    Reject,
+    Clip,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+enum ErrorKind {
+    Syntax(&'static str),
+    OutOfBounds(GridSize),
+}
+
+#[derive(Debug, Eq, PartialEq)]
+struct InstructionError {
+    line_number: usize,
+    line: String,
+    kind: ErrorKind,
+}
 
 struct BinaryLights {
+    size: GridSize,
     lights: Vec<bool>,
 }
 
 struct DimmableLights {
+    size: GridSize,
     lights: Vec<isize>,
 }
 
+// Only stores one light per cell between consecutive instruction boundaries,
+// so the grid can be arbitrarily large as long as there are few instructions.
+// Masked instructions split every cell further into the residues of x + y
+// modulo the period all the masks repeat with. That period never exceeds the
+// largest x + y on the grid, past which each residue is a single diagonal.
+struct CompressedLights<L> {
+    xs: Vec<usize>,
+    ys: Vec<usize>,
+    period: usize,
+    lights: Vec<L>,
+}
+
+// Summed-area table, so the sum over any rectangle takes four lookups.
+struct PrefixSums {
+    width: usize,
+    sums: Vec<usize>,
+}
+
+struct Snapshot {
+    lit: PrefixSums,
+    brightness: PrefixSums,
+}
+
+// Answers queries about the state after any number of the instructions. The
+// lights are kept after the last point in time that got asked about, so
+// moving forward only replays the instructions in between, and only the most
+// recent snapshot is kept around.
+struct LightHistory<'a> {
+    size: GridSize,
+    instructions: &'a [Instruction],
+    applied: usize,
+    binary_lights: BinaryLights,
+    dimmable_lights: DimmableLights,
+    snapshot: Option<(usize, Snapshot)>,
+}
+
 #[derive(Debug, Eq, PartialEq)]
+struct Query {
+    min: (usize, usize),
+    max: (usize, usize),
+    at: Option<usize>,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+struct QueryResult {
+    lit: usize,
+    brightness: usize,
+}
+
+// Only the GIF and PNG export uses these, but they're built regardless so
+// that they stay tested.
+#[cfg_attr(not(feature = "save"), allow(dead_code))]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum ColorMap {
+    RandomTiles,
+    Greyscale,
+    Heat,
+}
+
+// Assigns every light a shade (the tile color for RandomTiles) and every shade
+// a fixed number of brightness levels, so the whole thing fits into the 256
+// colors of a GIF palette.
+#[cfg_attr(not(feature = "save"), allow(dead_code))]
+struct Palette {
+    color_map: ColorMap,
+    tiles: Vec<usize>,
+    tiles_per_row: usize,
+}
+
+#[cfg(feature = "save")]
+struct SaveOptions {
+    color_map: ColorMap,
+    seed: usize,
+    animation_step: Option<usize>,
+}
+
+trait Light: Clone + Default {
+    fn apply(&mut self, operation: &Operation);
+    fn brightness(&self) -> usize;
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
 enum Operation {
     Toggle,
     TurnOff,
     TurnOn,
+    Set(usize),
+    Dim(usize),
+    Brighten(usize),
 }
 
+// An instruction with `every` set to n only affects the lights whose
+// Manhattan distance to `min` is a multiple of n.
 #[derive(Debug, Eq, PartialEq)]
 struct Instruction {
     operation: Operation,
     min: (usize, usize),
     max: (usize, usize),
+    every: usize,
+}
+
+impl GridSize {
+    fn new(width: usize, height: usize) -> Self {
+        GridSize { width, height }
+    }
+
+    fn parse(input: &str) -> Option<Self> {
+        let index = input.find('x')?;
+        let width = usize::from_str(&input[..index]).ok()?;
+        let height = usize::from_str(&input[index + 1..]).ok()?;
+        Some(GridSize::new(width, height))
+    }
+
+    fn area(&self) -> usize {
+        self.width * self.height
+    }
+
+    fn contains(&self, (x, y): (usize, usize)) -> bool {
+        x < self.width && y < self.height
+    }
+}
+
+impl Default for GridSize {
+    fn default() -> Self {
+        GridSize::new(1000, 1000)
+    }
+}
+
+impl fmt::Display for GridSize {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}x{}", self.width, self.height)
+    }
+}
+
+impl fmt::Display for InstructionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            Syntax(message) => write!(f, "Line {}: {}: {}", self.line_number, message, self.line),
+            OutOfBounds(size) => {
+                write!(f,
+                       "Line {} reaches outside of the {} grid: {}",
+                       self.line_number,
+                       size,
+                       self.line)
+            }
+        }
+    }
 }
 
 impl Instruction {
-    fn parse_coordinate(pair: &str) -> (usize, usize) {
-        let index = pair.find(',').unwrap();
+    fn parse_coordinate(pair: &str) -> Result<(usize, usize), &'static str> {
+        let index = pair.find(',').ok_or("Expected a coordinate like 0,0")?;
         let splits = pair.split_at(index);
-        let x = usize::from_str(splits.0).unwrap();
-        let y = usize::from_str(&splits.1[1..]).unwrap();
-        (x, y)
-    }
-
-    fn parse(line: &str) -> Self {
-        let mut splits = line.split_whitespace();
-        let op1 = splits.next().unwrap();
-        let operation = if op1 == "turn" {
-            let op2 = splits.next().unwrap();
-            if op2 == "on" {
-                TurnOn
-            } else {
-                TurnOff
+        let x = usize::from_str(splits.0).map_err(|_| "Couldn't parse the x coordinate")?;
+        let y = usize::from_str(&splits.1[1..]).map_err(|_| "Couldn't parse the y coordinate")?;
+        Ok((x, y))
+    }
+
+    // Amounts have to fit the brightness of a dimmable light.
+    fn parse_amount(word: Option<&str>) -> Result<usize, &'static str> {
+        let amount = word.and_then(|w| usize::from_str(w).ok()).ok_or("Expected an amount")?;
+        if amount > isize::MAX as usize {
+            return Err("The amount is too large");
+        }
+        Ok(amount)
+    }
+
+    fn parse_every(word: &str) -> Result<usize, &'static str> {
+        let digits = word.trim_end_matches(|c: char| c.is_alphabetic());
+        match (usize::from_str(digits), &word[digits.len()..]) {
+            (Ok(n), "" | "st" | "nd" | "rd" | "th") if n > 0 => Ok(n),
+            _ => Err("Expected a positive ordinal like 2nd after 'every'"),
+        }
+    }
+
+    fn parse(line: &str) -> Result<Self, &'static str> {
+        let mut splits = line.split_whitespace().peekable();
+        let operation = match splits.next() {
+            Some("turn") => {
+                match splits.next() {
+                    Some("on") => TurnOn,
+                    Some("off") => TurnOff,
+                    _ => return Err("Expected 'on' or 'off' after 'turn'"),
+                }
             }
+            Some("toggle") => Toggle,
+            Some("set") => Set(Self::parse_amount(splits.next())?),
+            Some("dim") => Dim(Self::parse_amount(splits.next())?),
+            Some("brighten") => Brighten(Self::parse_amount(splits.next())?),
+            _ => return Err("Unknown operation"),
+        };
+        let every = if splits.peek() == Some(&"every") {
+            splits.next();
+            Self::parse_every(splits.next().ok_or("Expected an ordinal after 'every'")?)?
         } else {
-            Toggle
+            1
         };
-        let min = Self::parse_coordinate(splits.next().unwrap());
-        let _through = splits.next();
-        let max = Self::parse_coordinate(splits.next().unwrap());
-        Instruction {
-            operation: operation,
-            min: min,
-            max: max,
+        let min = Self::parse_coordinate(splits.next().ok_or("Expected a coordinate")?)?;
+        if splits.next() != Some("through") {
+            return Err("Expected 'through'");
+        }
+        let max = Self::parse_coordinate(splits.next().ok_or("Expected a coordinate")?)?;
+        if splits.next().is_some() {
+            return Err("Unexpected text after the instruction");
+        }
+        Ok(Instruction {
+            operation,
+            min,
+            max,
+            every,
+        })
+    }
+
+    fn affects(&self, (x, y): (usize, usize)) -> bool {
+        (x - self.min.0 + y - self.min.1).is_multiple_of(self.every)
+    }
+
+    fn is_within(&self, size: GridSize) -> bool {
+        size.contains(self.min) && size.contains(self.max)
+    }
+
+    fn clip(&self, size: GridSize) -> Option<Self> {
+        if self.min.0 >= size.width || self.min.1 >= size.height {
+            None
+        } else {
+            Some(Instruction {
+                operation: self.operation,
+                min: self.min,
+                max: (cmp::min(self.max.0, size.width - 1), cmp::min(self.max.1, size.height - 1)),
+                every: self.every,
+            })
         }
     }
 }
 
+impl Light for bool {
+    fn apply(&mut self, operation: &Operation) {
+        // A binary light is either at full or zero brightness, so dimming or
+        // brightening it by any amount at all switches it off or on.
+        *self = match *operation {
+            TurnOn | Brighten(1..) => true,
+            TurnOff | Dim(1..) => false,
+            Toggle => !*self,
+            Set(level) => level > 0,
+            Dim(0) | Brighten(0) => *self,
+        };
+    }
+
+    fn brightness(&self) -> usize {
+        *self as usize
+    }
+}
+
+impl Light for isize {
+    fn apply(&mut self, operation: &Operation) {
+        *self = cmp::max(0,
+                         match *operation {
+                             Set(level) => level as isize,
+                             TurnOn => self.saturating_add(1),
+                             TurnOff => self.saturating_sub(1),
+                             Toggle => self.saturating_add(2),
+                             Dim(amount) => self.saturating_sub(amount as isize),
+                             Brighten(amount) => self.saturating_add(amount as isize),
+                         });
+    }
+
+    fn brightness(&self) -> usize {
+        *self as usize
+    }
+}
+
 impl BinaryLights {
-    fn new() -> Self {
-        BinaryLights { lights: vec![false; 1_000_000] }
+    fn new(size: GridSize) -> Self {
+        BinaryLights {
+            size,
+            lights: vec![false; size.area()],
+        }
     }
 
     fn apply_instruction(&mut self, instruction: &Instruction) {
-        for y in instruction.min.0..instruction.max.0 + 1 {
-            for x in instruction.min.1..instruction.max.1 + 1 {
-                self.lights[x + 1000 * y] = match instruction.operation {
-                    TurnOn => true,
-                    TurnOff => false,
-                    Toggle => !self.lights[x + 1000 * y],
-                };
+        assert!(instruction.is_within(self.size), "Instruction is outside of the grid");
+        for y in instruction.min.1..instruction.max.1 + 1 {
+            for x in instruction.min.0..instruction.max.0 + 1 {
+                if instruction.affects((x, y)) {
+                    self.lights[x + self.size.width * y].apply(&instruction.operation);
+                }
             }
         }
     }
@@ -102,20 +364,20 @@ impl BinaryLights {
 }
 
 impl DimmableLights {
-    fn new() -> Self {
-        DimmableLights { lights: vec![0; 1_000_000] }
+    fn new(size: GridSize) -> Self {
+        DimmableLights {
+            size,
+            lights: vec![0; size.area()],
+        }
     }
 
     fn apply_instruction(&mut self, instruction: &Instruction) {
-        for y in instruction.min.0..instruction.max.0 + 1 {
-            for x in instruction.min.1..instruction.max.1 + 1 {
-                self.lights[x + 1000 * y] = cmp::max(0,
-                                                     self.lights[x + 1000 * y] +
-                                                     match instruction.operation {
-                                                         TurnOn => 1,
-                                                         TurnOff => -1,
-                                                         Toggle => 2,
-                                                     });
+        assert!(instruction.is_within(self.size), "Instruction is outside of the grid");
+        for y in instruction.min.1..instruction.max.1 + 1 {
+            for x in instruction.min.0..instruction.max.0 + 1 {
+                if instruction.affects((x, y)) {
+                    self.lights[x + self.size.width * y].apply(&instruction.operation);
+                }
             }
         }
     }
@@ -133,33 +395,370 @@ impl DimmableLights {
     }
 
     #[cfg(feature = "save")]
-    fn write_to_image(&self) -> RgbaImage {
-        let mut image = RgbaImage::new(1000, 1000);
-
-        let base_colors = [(255.0, 0.0, 0.0),
-                           (255.0, 0.0, 0.0),
-                           (0.0, 255.0, 0.0),
-                           (255.0, 255.0, 0.0),
-                           (255.0, 255.0, 0.0),
-                           (255.0, 255.0, 255.0),
-                           (0.0, 255.0, 255.0)];
-        let range = Range::new(0usize, base_colors.len());
-        let mut rng = rand::thread_rng();
-
-        let color_grid = (0..10000)
-                             .map(|_| base_colors[range.ind_sample(&mut rng)])
-                             .collect::<Vec<_>>();
+    fn get_max_brightness(&self) -> f32 {
+        cmp::max(1, self.lights.iter().fold(0, |a, &l| cmp::max(a, l))) as f32
+    }
 
-        let max_brightness = self.lights.iter().fold(0, |a, &l| cmp::max(a, l)) as f32;
+    #[cfg(feature = "save")]
+    fn write_to_image(&self, palette: &Palette) -> RgbaImage {
+        let width = self.size.width;
+        let mut image = RgbaImage::new(width as u32, self.size.height as u32);
+        let max_brightness = self.get_max_brightness();
 
         for (x, y, pixel) in image.enumerate_pixels_mut() {
-            let value = self.lights[(x + 1000 * y) as usize] as f32 / max_brightness;
-            let (r, g, b) = color_grid[((x / 10) + 100 * (y / 10)) as usize];
-            pixel.data = [(value * r) as u8, (value * g) as u8, (value * b) as u8, 0xFF];
+            let (x, y) = (x as usize, y as usize);
+            let value = self.lights[x + width * y] as f32 / max_brightness;
+            let (r, g, b) = palette.get_color(palette.get_shade(x, y), value);
+            pixel.0 = [r as u8, g as u8, b as u8, 0xFF];
         }
 
         image::imageops::blur(&image, 5.0)
     }
+
+    #[cfg(feature = "save")]
+    fn write_to_frame(&self, palette: &Palette) -> Frame<'static> {
+        let width = self.size.width;
+        let max_brightness = self.get_max_brightness();
+
+        let buffer = self.lights
+                         .iter()
+                         .enumerate()
+                         .map(|(i, &l)| {
+                             palette.get_index(i % width, i / width, l as f32 / max_brightness)
+                         })
+                         .collect::<Vec<_>>();
+
+        Frame {
+            width: width as u16,
+            height: self.size.height as u16,
+            delay: 5,
+            buffer: Cow::Owned(buffer),
+            ..Frame::default()
+        }
+    }
+}
+
+const BASE_COLORS: [(f32, f32, f32); 7] = [(255.0, 0.0, 0.0),
+                                           (255.0, 0.0, 0.0),
+                                           (0.0, 255.0, 0.0),
+                                           (255.0, 255.0, 0.0),
+                                           (255.0, 255.0, 0.0),
+                                           (255.0, 255.0, 255.0),
+                                           (0.0, 255.0, 255.0)];
+
+#[cfg_attr(not(feature = "save"), allow(dead_code))]
+impl ColorMap {
+    fn parse(input: &str) -> Option<Self> {
+        match input {
+            "random" => Some(RandomTiles),
+            "grey" | "gray" => Some(Greyscale),
+            "heat" => Some(Heat),
+            _ => None,
+        }
+    }
+}
+
+#[cfg_attr(not(feature = "save"), allow(dead_code))]
+impl Palette {
+    fn new(color_map: ColorMap, size: GridSize, seed: usize) -> Self {
+        // xorshift64*, which is all it takes for the tiles to only depend on
+        // the seed.
+        let mut state = (seed as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1;
+        let mut next_tile = || {
+            state ^= state >> 12;
+            state ^= state << 25;
+            state ^= state >> 27;
+            (state.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 32) as usize % BASE_COLORS.len()
+        };
+
+        let tiles_per_row = size.width.div_ceil(10);
+        let tiles = (0..tiles_per_row * size.height.div_ceil(10)).map(|_| next_tile()).collect();
+
+        Palette {
+            color_map,
+            tiles,
+            tiles_per_row,
+        }
+    }
+
+    fn get_shade_count(&self) -> usize {
+        match self.color_map {
+            RandomTiles => BASE_COLORS.len(),
+            Greyscale | Heat => 1,
+        }
+    }
+
+    fn get_level_count(&self) -> usize {
+        256 / self.get_shade_count()
+    }
+
+    fn get_shade(&self, x: usize, y: usize) -> usize {
+        match self.color_map {
+            RandomTiles => self.tiles[(x / 10) + self.tiles_per_row * (y / 10)],
+            Greyscale | Heat => 0,
+        }
+    }
+
+    fn get_color(&self, shade: usize, value: f32) -> (f32, f32, f32) {
+        let channel = |offset: f32| 255.0 * (3.0 * value - offset).clamp(0.0, 1.0);
+        match self.color_map {
+            RandomTiles => {
+                let (r, g, b) = BASE_COLORS[shade];
+                (value * r, value * g, value * b)
+            }
+            Greyscale => (255.0 * value, 255.0 * value, 255.0 * value),
+            Heat => (channel(0.0), channel(1.0), channel(2.0)),
+        }
+    }
+
+    fn get_index(&self, x: usize, y: usize, value: f32) -> u8 {
+        let levels = self.get_level_count();
+        let level = (value * (levels - 1) as f32).round() as usize;
+        (self.get_shade(x, y) * levels + level) as u8
+    }
+
+    fn get_gif_palette(&self) -> Vec<u8> {
+        let levels = self.get_level_count();
+        let mut palette = Vec::new();
+        for shade in 0..self.get_shade_count() {
+            for level in 0..levels {
+                let (r, g, b) = self.get_color(shade, level as f32 / (levels - 1) as f32);
+                palette.extend(&[r as u8, g as u8, b as u8]);
+            }
+        }
+        palette
+    }
+}
+
+// The compressed grid needs a light for every cell and every residue of the
+// masks' period, which stops being worth it well before running out of memory.
+const MAX_COMPRESSED_LIGHTS: usize = 1 << 25;
+
+impl<L: Light> CompressedLights<L> {
+    fn new<'a, I>(instructions: I) -> Result<Self, &'static str>
+        where I: IntoIterator<Item = &'a Instruction>
+    {
+        let too_large = "The masks repeat too rarely for the compressed grid.";
+        let instructions = instructions.into_iter().collect::<Vec<_>>();
+        let diagonals = instructions.iter()
+                                    .map(|i| (i.max.0).saturating_add(i.max.1).saturating_add(1))
+                                    .max()
+                                    .unwrap_or(1);
+        let mut xs = Vec::new();
+        let mut ys = Vec::new();
+        let mut period = 1;
+        for instruction in instructions {
+            xs.push(instruction.min.0);
+            xs.push(instruction.max.0 + 1);
+            ys.push(instruction.min.1);
+            ys.push(instruction.max.1 + 1);
+            period = (period / gcd(period, instruction.every))
+                         .checked_mul(instruction.every)
+                         .map_or(diagonals, |p| cmp::min(p, diagonals));
+            if period > MAX_COMPRESSED_LIGHTS {
+                return Err(too_large);
+            }
+        }
+        xs.sort();
+        xs.dedup();
+        ys.sort();
+        ys.dedup();
+
+        let cells = xs.len().saturating_sub(1) * ys.len().saturating_sub(1);
+        let lights = cells.checked_mul(period)
+                          .filter(|&l| l <= MAX_COMPRESSED_LIGHTS)
+                          .ok_or(too_large)?;
+        Ok(CompressedLights {
+            lights: vec![L::default(); lights],
+            xs,
+            ys,
+            period,
+        })
+    }
+
+    // How many (x, y) with x < a and y < b have x + y in the residue class.
+    // Every full period of x meets b % period values of y, and the rest of
+    // x meets those in the cyclic window of b % period residues ending at the
+    // residue, which is at most two plain ranges.
+    fn residue_count(&self, (a, b): (usize, usize), residue: usize) -> usize {
+        let period = self.period;
+        let (rest_a, rest_b) = (a % period, b % period);
+        let overlap = |start: usize, end: usize| cmp::min(end, rest_a).saturating_sub(start);
+        let rest = if rest_b <= residue + 1 {
+            overlap(residue + 1 - rest_b, residue + 1)
+        } else {
+            overlap(0, residue + 1) + overlap(period + residue + 1 - rest_b, period)
+        };
+        a * (b / period) + a / period * rest_b + rest
+    }
+
+    fn compressed_range(coordinates: &[usize], min: usize, max: usize) -> (usize, usize) {
+        let find = |c| {
+            coordinates.binary_search(&c)
+                       .expect("The instruction wasn't used to build the compressed grid")
+        };
+        (find(min), find(max + 1))
+    }
+
+    fn apply_instruction(&mut self, instruction: &Instruction) {
+        let (x0, x1) = Self::compressed_range(&self.xs, instruction.min.0, instruction.max.0);
+        let (y0, y1) = Self::compressed_range(&self.ys, instruction.min.1, instruction.max.1);
+        let width = self.xs.len() - 1;
+        let offset = (instruction.min.0 + instruction.min.1) % self.period;
+        for y in y0..y1 {
+            for x in x0..x1 {
+                for residue in 0..self.period {
+                    let distance = (residue + self.period - offset) % self.period;
+                    if distance.is_multiple_of(instruction.every) {
+                        let index = (x + width * y) * self.period + residue;
+                        self.lights[index].apply(&instruction.operation);
+                    }
+                }
+            }
+        }
+    }
+
+    fn apply_instructions<'a, I>(&mut self, instructions: I)
+        where I: IntoIterator<Item = &'a Instruction>
+    {
+        for instruction in instructions {
+            self.apply_instruction(instruction);
+        }
+    }
+
+    fn get_active_lights_count(&self) -> usize {
+        let width = self.xs.len().saturating_sub(1);
+        let mut count = 0;
+        for (i, lights) in self.lights.chunks(self.period).enumerate() {
+            let (x, y) = (i % width, i / width);
+            let (x0, x1, y0, y1) = (self.xs[x], self.xs[x + 1], self.ys[y], self.ys[y + 1]);
+            for (residue, light) in lights.iter().enumerate() {
+                let cells = self.residue_count((x1, y1), residue) +
+                            self.residue_count((x0, y0), residue) -
+                            self.residue_count((x0, y1), residue) -
+                            self.residue_count((x1, y0), residue);
+                count += light.brightness() * cells;
+            }
+        }
+        count
+    }
+}
+
+impl PrefixSums {
+    fn new<I>(size: GridSize, values: I) -> Self
+        where I: IntoIterator<Item = usize>
+    {
+        let width = size.width + 1;
+        let mut sums = vec![0; width * (size.height + 1)];
+        for (i, value) in values.into_iter().enumerate() {
+            let (x, y) = (i % size.width + 1, i / size.width + 1);
+            sums[x + width * y] = value + sums[x - 1 + width * y] + sums[x + width * (y - 1)] -
+                                  sums[x - 1 + width * (y - 1)];
+        }
+        PrefixSums { width, sums }
+    }
+
+    fn sum(&self, min: (usize, usize), max: (usize, usize)) -> usize {
+        let (x0, y0) = min;
+        let (x1, y1) = (max.0 + 1, max.1 + 1);
+        let w = self.width;
+        self.sums[x1 + w * y1] + self.sums[x0 + w * y0] - self.sums[x0 + w * y1] -
+        self.sums[x1 + w * y0]
+    }
+}
+
+impl<'a> LightHistory<'a> {
+    fn new(size: GridSize, instructions: &'a [Instruction]) -> Self {
+        LightHistory {
+            size,
+            instructions,
+            applied: 0,
+            binary_lights: BinaryLights::new(size),
+            dimmable_lights: DimmableLights::new(size),
+            snapshot: None,
+        }
+    }
+
+    fn snapshot(&mut self, count: usize) -> &Snapshot {
+        if self.snapshot.as_ref().is_none_or(|&(at, _)| at != count) {
+            if count < self.applied {
+                self.binary_lights = BinaryLights::new(self.size);
+                self.dimmable_lights = DimmableLights::new(self.size);
+                self.applied = 0;
+            }
+            let instructions = &self.instructions[self.applied..count];
+            self.binary_lights.apply_instructions(instructions);
+            self.dimmable_lights.apply_instructions(instructions);
+            self.applied = count;
+
+            let binary_lights = self.binary_lights.lights.iter().map(|l| l.brightness());
+            let dimmable_lights = self.dimmable_lights.lights.iter().map(|l| l.brightness());
+            self.snapshot = Some((count,
+                                  Snapshot {
+                                      lit: PrefixSums::new(self.size, binary_lights),
+                                      brightness: PrefixSums::new(self.size, dimmable_lights),
+                                  }));
+        }
+        &self.snapshot.as_ref().unwrap().1
+    }
+
+    fn query(&mut self, query: &Query) -> Result<QueryResult, &'static str> {
+        let count = query.at.unwrap_or(self.instructions.len());
+        if count > self.instructions.len() {
+            Err("There aren't that many instructions.")
+        } else if !self.size.contains(query.min) || !self.size.contains(query.max) {
+            Err("The rectangle reaches outside of the grid.")
+        } else if query.min.0 > query.max.0 || query.min.1 > query.max.1 {
+            Ok(QueryResult {
+                lit: 0,
+                brightness: 0,
+            })
+        } else {
+            let snapshot = self.snapshot(count);
+            Ok(QueryResult {
+                lit: snapshot.lit.sum(query.min, query.max),
+                brightness: snapshot.brightness.sum(query.min, query.max),
+            })
+        }
+    }
+}
+
+impl Query {
+    fn parse<'a, I>(words: I) -> Option<Self>
+        where I: IntoIterator<Item = &'a str>
+    {
+        let mut words = words.into_iter();
+        let min = Instruction::parse_coordinate(words.next()?).ok()?;
+        if words.next()? != "through" {
+            return None;
+        }
+        let max = Instruction::parse_coordinate(words.next()?).ok()?;
+        let at = match words.next() {
+            Some("at") => Some(usize::from_str(words.next()?).ok()?),
+            Some(_) => return None,
+            None => None,
+        };
+        Some(Query { min, max, at })
+    }
+}
+
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f,
+               "{},{} through {},{}",
+               self.min.0,
+               self.min.1,
+               self.max.0,
+               self.max.1)?;
+        if let Some(at) = self.at {
+            write!(f, " at {}", at)?;
+        }
+        Ok(())
+    }
+}
+
+fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 { a } else { gcd(b, a % b) }
 }
 
 fn read_file(path: &Path) -> Vec<String> {
@@ -169,79 +768,258 @@ fn read_file(path: &Path) -> Vec<String> {
     input.lines().into_iter().map(|s| s.to_owned()).collect()
 }
 
-fn parse_instructions<'a, I>(lines: I) -> Vec<Instruction>
+fn parse_instructions<'a, I>(lines: I,
+                              size: GridSize,
+                              policy: BoundsPolicy)
+                              -> Result<Vec<Instruction>, InstructionError>
     where I: IntoIterator<Item = &'a String>
 {
-    lines.into_iter().map(|l| Instruction::parse(l)).collect()
+    let mut instructions = Vec::new();
+    for (index, line) in lines.into_iter().enumerate() {
+        let error = |kind| {
+            InstructionError {
+                line_number: index + 1,
+                line: line.clone(),
+                kind,
+            }
+        };
+        let code = line.split('#').next().unwrap().trim();
+        if code.is_empty() {
+            continue;
+        }
+        let instruction = Instruction::parse(code).map_err(|e| error(Syntax(e)))?;
+        if instruction.is_within(size) {
+            instructions.push(instruction);
+        } else if policy == Clip {
+            instructions.extend(instruction.clip(size));
+        } else {
+            return Err(error(OutOfBounds(size)));
+        }
+    }
+    Ok(instructions)
+}
+
+struct Options {
+    size: GridSize,
+    policy: BoundsPolicy,
+    compressed: bool,
+    queries: Vec<Query>,
+    #[cfg(feature = "save")]
+    save: SaveOptions,
+}
+
+impl Options {
+    fn parse<I>(arguments: I) -> Self
+        where I: IntoIterator<Item = String>
+    {
+        let mut options = Options {
+            size: GridSize::default(),
+            policy: Reject,
+            compressed: false,
+            queries: Vec::new(),
+            #[cfg(feature = "save")]
+            save: SaveOptions {
+                color_map: RandomTiles,
+                seed: 0,
+                animation_step: None,
+            },
+        };
+        let mut arguments = arguments.into_iter();
+        while let Some(argument) = arguments.next() {
+            match argument.as_str() {
+                "--compressed" => options.compressed = true,
+                "--clip" => options.policy = Clip,
+                "--size" => {
+                    options.size = arguments.next()
+                                            .and_then(|s| GridSize::parse(&s))
+                                            .expect("Expected a grid size like 1000x1000")
+                }
+                "query" => {
+                    let words = arguments.by_ref().take_while(|a| a != ";").collect::<Vec<_>>();
+                    let query = Query::parse(words.iter().map(|w| w.as_str()))
+                                    .expect("Expected a query like 0,0 through 9,9 [at 5]");
+                    options.queries.push(query);
+                }
+                #[cfg(feature = "save")]
+                "--colors" => {
+                    options.save.color_map = arguments.next()
+                                                      .and_then(|s| ColorMap::parse(&s))
+                                                      .expect("Expected random, grey or heat")
+                }
+                #[cfg(feature = "save")]
+                "--seed" => {
+                    options.save.seed = arguments.next()
+                                                 .and_then(|s| usize::from_str(&s).ok())
+                                                 .expect("Expected a seed")
+                }
+                #[cfg(feature = "save")]
+                "--animate" => {
+                    let step = arguments.next()
+                                        .and_then(|s| usize::from_str(&s).ok())
+                                        .filter(|&n| n > 0)
+                                        .expect("Expected the number of instructions per frame");
+                    options.save.animation_step = Some(step);
+                }
+                _ => panic!("Unknown argument {}", argument),
+            }
+        }
+        options
+    }
 }
 
 #[cfg(not(feature = "save"))]
-fn save_lights(_: &DimmableLights) {}
+fn save_lights(_: &DimmableLights, _: &Options, _: &[Instruction]) {}
 
 #[cfg(feature = "save")]
-fn save_lights(lights: &DimmableLights) {
-    let image = lights.write_to_image();
+fn save_lights(lights: &DimmableLights, options: &Options, instructions: &[Instruction]) {
+    let palette = Palette::new(options.save.color_map, options.size, options.save.seed);
+
+    let image = lights.write_to_image(&palette);
     let _result = image.save("image.png");
 
     println!("Image saved");
+
+    if let Some(step) = options.save.animation_step {
+        match save_animation(Path::new("image.gif"), &palette, options.size, instructions, step) {
+            Ok(frames) => println!("Animation with {} frames saved", frames),
+            Err(err) => println!("Animation couldn't be saved: {}", err),
+        }
+    }
+}
+
+#[cfg(feature = "save")]
+fn save_animation(path: &Path,
+                  palette: &Palette,
+                  size: GridSize,
+                  instructions: &[Instruction],
+                  step: usize)
+                  -> Result<usize, EncodingError> {
+    if size.width > u16::MAX as usize || size.height > u16::MAX as usize {
+        let err = io::Error::new(io::ErrorKind::InvalidInput, "The grid is too large for a GIF");
+        return Err(err.into());
+    }
+
+    let file = File::create(path)?;
+    let mut encoder = Encoder::new(file,
+                                   size.width as u16,
+                                   size.height as u16,
+                                   &palette.get_gif_palette())?;
+    encoder.set_repeat(Repeat::Infinite)?;
+
+    let mut lights = DimmableLights::new(size);
+    let mut frames = 0;
+    for chunk in instructions.chunks(step) {
+        lights.apply_instructions(chunk);
+        encoder.write_frame(&lights.write_to_frame(palette))?;
+        frames += 1;
+    }
+
+    Ok(frames)
 }
 
 fn main() {
+    let options = Options::parse(args().skip(1));
     let lines = read_file(Path::new("input.txt"));
-    let instructions = parse_instructions(&lines);
+    let instructions = match parse_instructions(&lines, options.size, options.policy) {
+        Ok(instructions) => instructions,
+        Err(err) => {
+            println!("{}", err);
+            return;
+        }
+    };
 
-    let mut binary_lights = BinaryLights::new();
+    if !options.queries.is_empty() {
+        let mut history = LightHistory::new(options.size, &instructions);
+        for query in &options.queries {
+            match history.query(query) {
+                Ok(result) => {
+                    println!("{}: {} lit, {} total brightness",
+                             query,
+                             result.lit,
+                             result.brightness)
+                }
+                Err(err) => println!("{}: {}", query, err),
+            }
+        }
+        return;
+    }
+
+    if options.compressed {
+        let (mut binary_lights, mut dimmable_lights) =
+            match (CompressedLights::<bool>::new(&instructions),
+                   CompressedLights::<isize>::new(&instructions)) {
+                (Ok(binary_lights), Ok(dimmable_lights)) => (binary_lights, dimmable_lights),
+                (Err(err), _) | (_, Err(err)) => {
+                    println!("{}", err);
+                    return;
+                }
+            };
+        binary_lights.apply_instructions(&instructions);
+        println!("Active Binary Lights: {}", binary_lights.get_active_lights_count());
+
+        dimmable_lights.apply_instructions(&instructions);
+        println!("Active Dimmable Lights: {}", dimmable_lights.get_active_lights_count());
+        return;
+    }
+
+    let mut binary_lights = BinaryLights::new(options.size);
     binary_lights.apply_instructions(&instructions);
     let active_binary_lights = binary_lights.get_active_lights_count();
     println!("Active Binary Lights: {}", active_binary_lights);
 
-    let mut dimmable_lights = DimmableLights::new();
+    let mut dimmable_lights = DimmableLights::new(options.size);
     dimmable_lights.apply_instructions(&instructions);
     let active_dimmable_lights = dimmable_lights.get_active_lights_count();
     println!("Active Dimmable Lights: {}", active_dimmable_lights);
 
-    save_lights(&dimmable_lights);
+    save_lights(&dimmable_lights, &options, &instructions);
 }
 
 #[test]
 fn test_parsing() {
     assert_eq!(Instruction::parse("turn on 0,0 through 999,999"),
-               Instruction {
+               Ok(Instruction {
                    operation: TurnOn,
                    min: (0, 0),
                    max: (999, 999),
-               });
+                   every: 1,
+               }));
     assert_eq!(Instruction::parse("toggle 0,0 through 999,0"),
-               Instruction {
+               Ok(Instruction {
                    operation: Toggle,
                    min: (0, 0),
                    max: (999, 0),
-               });
+                   every: 1,
+               }));
     assert_eq!(Instruction::parse("turn off 499,499 through 500,500"),
-               Instruction {
+               Ok(Instruction {
                    operation: TurnOff,
                    min: (499, 499),
                    max: (500, 500),
-               });
+                   every: 1,
+               }));
 }
 
 #[test]
 fn test_instructions_on_binary_lights() {
-    let mut lights = BinaryLights::new();
+    let mut lights = BinaryLights::new(GridSize::default());
     let instruction1 = Instruction {
         operation: TurnOn,
         min: (0, 0),
         max: (999, 999),
+        every: 1,
     };
     let instruction2 = Instruction {
         operation: Toggle,
         min: (0, 0),
         max: (999, 0),
+        every: 1,
     };
     let instruction3 = Instruction {
         operation: TurnOff,
         min: (499, 499),
         max: (500, 500),
+        every: 1,
     };
 
     lights.apply_instruction(&instruction1);
@@ -257,21 +1035,24 @@ fn test_instructions_on_binary_lights() {
 
 #[test]
 fn test_instructions_on_dimmable_lights() {
-    let mut lights = DimmableLights::new();
+    let mut lights = DimmableLights::new(GridSize::default());
     let instruction1 = Instruction {
         operation: TurnOn,
         min: (0, 0),
         max: (999, 999),
+        every: 1,
     };
     let instruction2 = Instruction {
         operation: Toggle,
         min: (0, 0),
         max: (999, 0),
+        every: 1,
     };
     let instruction3 = Instruction {
         operation: TurnOff,
         min: (499, 499),
         max: (500, 500),
+        every: 1,
     };
 
     lights.apply_instruction(&instruction1);
@@ -283,3 +1064,309 @@ fn test_instructions_on_dimmable_lights() {
     lights.apply_instruction(&instruction3);
     assert_eq!(lights.get_active_lights_count(), 1001996);
 }
+
+
+#[test]
+fn test_compressed_lights_match_grid() {
+    let lines = read_file(Path::new("input.txt"));
+    let instructions = parse_instructions(&lines, GridSize::default(), Reject).unwrap();
+
+    let mut binary_lights = BinaryLights::new(GridSize::default());
+    binary_lights.apply_instructions(&instructions);
+    let mut compressed_binary_lights = CompressedLights::<bool>::new(&instructions).unwrap();
+    compressed_binary_lights.apply_instructions(&instructions);
+    assert_eq!(compressed_binary_lights.get_active_lights_count(),
+               binary_lights.get_active_lights_count());
+
+    let mut dimmable_lights = DimmableLights::new(GridSize::default());
+    dimmable_lights.apply_instructions(&instructions);
+    let mut compressed_dimmable_lights = CompressedLights::<isize>::new(&instructions).unwrap();
+    compressed_dimmable_lights.apply_instructions(&instructions);
+    assert_eq!(compressed_dimmable_lights.get_active_lights_count() as isize,
+               dimmable_lights.get_active_lights_count());
+}
+
+#[test]
+fn test_compressed_lights_on_huge_grid() {
+    let instructions = vec![Instruction::parse("turn on 0,0 through 999999,999999").unwrap(),
+                            Instruction::parse("toggle 0,0 through 999999,0").unwrap(),
+                            Instruction::parse("turn off 499999,499999 through 500000,500000").unwrap()];
+
+    let mut lights = CompressedLights::<bool>::new(&instructions).unwrap();
+    lights.apply_instructions(&instructions);
+    assert_eq!(lights.get_active_lights_count(), 1_000_000 * 999_999 - 4);
+
+    let mut lights = CompressedLights::<isize>::new(&instructions).unwrap();
+    lights.apply_instructions(&instructions);
+    assert_eq!(lights.get_active_lights_count(), 1_000_000 * 1_000_000 + 2_000_000 - 4);
+}
+
+#[test]
+fn test_bounds_policies() {
+    let lines = vec!["turn on 0,0 through 4,1".to_owned(),
+                     "toggle 3,0 through 9,9".to_owned(),
+                     "turn off 20,20 through 30,30".to_owned()];
+    let size = GridSize::new(5, 2);
+
+    assert_eq!(parse_instructions(&lines, size, Reject).err(),
+               Some(InstructionError {
+                   line_number: 2,
+                   line: "toggle 3,0 through 9,9".to_owned(),
+                   kind: OutOfBounds(size),
+               }));
+
+    let instructions = parse_instructions(&lines, size, Clip).unwrap();
+    assert_eq!(instructions,
+               vec![Instruction {
+                        operation: TurnOn,
+                        min: (0, 0),
+                        max: (4, 1),
+                        every: 1,
+                    },
+                    Instruction {
+                        operation: Toggle,
+                        min: (3, 0),
+                        max: (4, 1),
+                        every: 1,
+                    }]);
+
+    let mut lights = BinaryLights::new(size);
+    lights.apply_instructions(&instructions);
+    assert_eq!(lights.get_active_lights_count(), 6);
+}
+
+#[test]
+fn test_non_square_grid() {
+    let mut lights = DimmableLights::new(GridSize::new(3, 7));
+    lights.apply_instruction(&Instruction::parse("toggle 1,0 through 2,6").unwrap());
+    lights.apply_instruction(&Instruction::parse("turn on 0,5 through 2,6").unwrap());
+    assert_eq!(lights.get_active_lights_count(), 2 * 14 + 6);
+    assert_eq!(lights.lights[1 + 3 * 6], 3);
+    assert_eq!(lights.lights[2], 2);
+}
+
+#[test]
+fn test_rectangle_queries() {
+    let instructions = vec![Instruction::parse("turn on 0,0 through 9,9").unwrap(),
+                            Instruction::parse("toggle 2,2 through 5,5").unwrap(),
+                            Instruction::parse("turn off 0,0 through 3,3").unwrap()];
+    let mut history = LightHistory::new(GridSize::new(10, 10), &instructions);
+
+    let query = |text: &str| Query::parse(text.split_whitespace()).unwrap();
+
+    assert_eq!(history.query(&query("0,0 through 9,9")),
+               Ok(QueryResult {
+                   lit: 100 - 16 - 12,
+                   brightness: 100 + 32 - 16,
+               }));
+    assert_eq!(history.query(&query("2,2 through 5,5 at 1")),
+               Ok(QueryResult {
+                   lit: 16,
+                   brightness: 16,
+               }));
+    assert_eq!(history.query(&query("2,2 through 5,5 at 2")),
+               Ok(QueryResult {
+                   lit: 0,
+                   brightness: 48,
+               }));
+    assert_eq!(history.query(&query("0,0 through 9,9 at 0")),
+               Ok(QueryResult {
+                   lit: 0,
+                   brightness: 0,
+               }));
+    assert_eq!(history.applied, 0);
+    assert_eq!(history.query(&query("2,2 through 5,5 at 2")).unwrap().brightness, 48);
+    assert_eq!(history.applied, 2);
+    assert!(history.query(&query("0,0 through 10,9")).is_err());
+    assert!(history.query(&query("0,0 through 9,9 at 4")).is_err());
+    assert_eq!(Query::parse("0,0 to 9,9".split_whitespace()), None);
+}
+
+#[test]
+fn test_queries_match_full_grid() {
+    let lines = read_file(Path::new("input.txt"));
+    let instructions = parse_instructions(&lines, GridSize::default(), Reject).unwrap();
+    let mut history = LightHistory::new(GridSize::default(), &instructions);
+
+    let mut lights = DimmableLights::new(GridSize::default());
+    lights.apply_instructions(&instructions[..50]);
+
+    let query = Query::parse("100,200 through 600,700 at 50".split_whitespace()).unwrap();
+    let mut brightness = 0;
+    for y in 200..701 {
+        for x in 100..601 {
+            brightness += lights.lights[x + 1000 * y];
+        }
+    }
+    assert_eq!(history.query(&query).unwrap().brightness as isize, brightness);
+}
+
+#[test]
+fn test_palette_is_seeded() {
+    let size = GridSize::new(100, 50);
+    let first = Palette::new(RandomTiles, size, 7);
+    assert_eq!(first.tiles, Palette::new(RandomTiles, size, 7).tiles);
+    assert!(first.tiles != Palette::new(RandomTiles, size, 8).tiles);
+
+    for &color_map in &[RandomTiles, Greyscale, Heat] {
+        let palette = Palette::new(color_map, size, 0);
+        assert!(palette.get_gif_palette().len() <= 256 * 3);
+        assert_eq!(palette.get_index(0, 0, 0.0) as usize % palette.get_level_count(), 0);
+        assert_eq!(palette.get_index(99, 49, 1.0) as usize % palette.get_level_count(),
+                   palette.get_level_count() - 1);
+    }
+}
+
+#[test]
+fn test_parsing_extended_instructions() {
+    assert_eq!(Instruction::parse("set 5 1,2 through 3,4"),
+               Ok(Instruction {
+                   operation: Set(5),
+                   min: (1, 2),
+                   max: (3, 4),
+                   every: 1,
+               }));
+    assert_eq!(Instruction::parse("dim 2 every 3rd 0,0 through 9,9"),
+               Ok(Instruction {
+                   operation: Dim(2),
+                   min: (0, 0),
+                   max: (9, 9),
+                   every: 3,
+               }));
+    assert_eq!(Instruction::parse("toggle every 2nd 0,0 through 9,9").map(|i| i.every),
+               Ok(2));
+    assert_eq!(Instruction::parse("brighten 7 0,0 through 9,9").map(|i| i.operation),
+               Ok(Brighten(7)));
+
+    assert!(Instruction::parse("blink 0,0 through 9,9").is_err());
+    assert!(Instruction::parse("turn up 0,0 through 9,9").is_err());
+    assert!(Instruction::parse("set 0,0 through 9,9").is_err());
+    assert!(Instruction::parse("toggle every 0th 0,0 through 9,9").is_err());
+    assert!(Instruction::parse("toggle every 2xy 0,0 through 9,9").is_err());
+    assert!(Instruction::parse("toggle 0,0 to 9,9").is_err());
+    assert!(Instruction::parse("toggle 0,0 through 9,9 now").is_err());
+}
+
+#[test]
+fn test_comments_and_errors() {
+    let lines = vec!["# the light show".to_owned(),
+                     "".to_owned(),
+                     "turn on 0,0 through 9,9 # everything".to_owned(),
+                     "   ".to_owned(),
+                     "blink 0,0 through 9,9".to_owned()];
+    let size = GridSize::new(10, 10);
+
+    assert_eq!(parse_instructions(&lines[..4], size, Reject).map(|i| i.len()), Ok(1));
+    assert_eq!(parse_instructions(&lines, size, Reject).err(),
+               Some(InstructionError {
+                   line_number: 5,
+                   line: "blink 0,0 through 9,9".to_owned(),
+                   kind: Syntax("Unknown operation"),
+               }));
+    assert_eq!(parse_instructions(&lines, size, Reject).unwrap_err().to_string(),
+               "Line 5: Unknown operation: blink 0,0 through 9,9");
+}
+
+#[test]
+fn test_extreme_amounts() {
+    let size = GridSize::new(10, 10);
+    let lines = vec!["dim 9223372036854775808 0,0 through 0,0".to_owned()];
+    assert_eq!(parse_instructions(&lines, size, Reject).unwrap_err().kind,
+               Syntax("The amount is too large"));
+
+    let lines = vec![format!("dim {} 0,0 through 0,0", isize::MAX),
+                     format!("brighten {} 0,0 through 0,0", isize::MAX),
+                     format!("brighten {} 0,0 through 0,0", isize::MAX),
+                     "toggle 0,0 through 0,0".to_owned(),
+                     "dim 1 1,0 through 1,0".to_owned()];
+    let instructions = parse_instructions(&lines, size, Reject).unwrap();
+    let mut lights = DimmableLights::new(size);
+    lights.apply_instructions(&instructions);
+    assert_eq!(lights.lights[..2], [isize::MAX, 0]);
+
+    let mut lights = CompressedLights::<isize>::new(&instructions).unwrap();
+    lights.apply_instructions(&instructions);
+    assert_eq!(lights.get_active_lights_count(), isize::MAX as usize);
+}
+
+#[test]
+fn test_extended_operations() {
+    let lines = vec!["set 4 0,0 through 9,9".to_owned(),
+                     "dim 1 every 2nd 0,0 through 9,9".to_owned(),
+                     "brighten 3 every 3rd 1,0 through 9,9".to_owned(),
+                     "dim 10 0,0 through 9,0".to_owned(),
+                     "toggle every 2nd 0,1 through 9,9".to_owned()];
+    let size = GridSize::new(10, 10);
+    let instructions = parse_instructions(&lines, size, Reject).unwrap();
+
+    let mut binary_lights = BinaryLights::new(size);
+    binary_lights.apply_instructions(&instructions);
+    let mut dimmable_lights = DimmableLights::new(size);
+    dimmable_lights.apply_instructions(&instructions);
+
+    assert_eq!(binary_lights.lights[..4], [false, false, false, false]);
+    assert_eq!(binary_lights.lights[11..14], [false, false, true]);
+    assert_eq!(dimmable_lights.lights[..4], [0, 0, 0, 0]);
+    assert_eq!(dimmable_lights.lights[11..14], [3, 6, 6]);
+
+    let mut compressed_binary_lights = CompressedLights::<bool>::new(&instructions).unwrap();
+    compressed_binary_lights.apply_instructions(&instructions);
+    assert_eq!(compressed_binary_lights.get_active_lights_count(),
+               binary_lights.get_active_lights_count());
+
+    let mut compressed_dimmable_lights = CompressedLights::<isize>::new(&instructions).unwrap();
+    compressed_dimmable_lights.apply_instructions(&instructions);
+    assert_eq!(compressed_dimmable_lights.get_active_lights_count() as isize,
+               dimmable_lights.get_active_lights_count());
+}
+
+#[test]
+fn test_compressed_lights_limit() {
+    let size = GridSize::new(100_000_000, 100_000_000);
+    let lines = ["toggle every 97th 0,0 through 99999999,99999999",
+                 "toggle every 89th 0,0 through 99999999,99999999",
+                 "toggle every 83rd 0,0 through 99999999,99999999",
+                 "toggle every 79th 0,0 through 99999999,99999999"]
+                    .iter()
+                    .map(|l| l.to_string())
+                    .collect::<Vec<_>>();
+    let instructions = parse_instructions(&lines, size, Reject).unwrap();
+    assert!(CompressedLights::<bool>::new(&instructions[..3]).is_ok());
+    assert!(CompressedLights::<bool>::new(&instructions).is_err());
+
+    let lines = ["toggle every 4294967291st 0,0 through 99999999,99999999",
+                 "toggle every 4294967279th 0,0 through 99999999,99999999",
+                 "toggle every 4294967231st 0,0 through 99999999,99999999"]
+                    .iter()
+                    .map(|l| l.to_string())
+                    .collect::<Vec<_>>();
+    let instructions = parse_instructions(&lines, size, Reject).unwrap();
+    assert!(CompressedLights::<isize>::new(&instructions).is_err());
+}
+
+#[test]
+fn test_compressed_lights_with_rare_masks() {
+    let size = GridSize::new(1000, 1000);
+    let lines = ["toggle every 1000000th 0,0 through 9,9",
+                 "turn on every 100000th 3,4 through 999,999",
+                 "brighten 2 every 1999th 0,0 through 999,999",
+                 "toggle every 7th 1,2 through 500,600"]
+                    .iter()
+                    .map(|l| l.to_string())
+                    .collect::<Vec<_>>();
+    let instructions = parse_instructions(&lines, size, Reject).unwrap();
+
+    let mut binary_lights = BinaryLights::new(size);
+    binary_lights.apply_instructions(&instructions);
+    let mut compressed_binary_lights = CompressedLights::<bool>::new(&instructions).unwrap();
+    compressed_binary_lights.apply_instructions(&instructions);
+    assert_eq!(compressed_binary_lights.get_active_lights_count(),
+               binary_lights.get_active_lights_count());
+
+    let mut dimmable_lights = DimmableLights::new(size);
+    dimmable_lights.apply_instructions(&instructions);
+    let mut compressed_dimmable_lights = CompressedLights::<isize>::new(&instructions).unwrap();
+    compressed_dimmable_lights.apply_instructions(&instructions);
+    assert_eq!(compressed_dimmable_lights.get_active_lights_count() as isize,
+               dimmable_lights.get_active_lights_count());
+}
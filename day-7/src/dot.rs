@@ -0,0 +1,132 @@
+use std::collections::HashSet;
+use std::fmt::Write;
+
+use {CPU, Expression, Word};
+use Expression::*;
+
+struct DotWriter {
+    output: String,
+    nodes: usize,
+}
+
+impl DotWriter {
+    fn new_node(&mut self) -> String {
+        self.nodes += 1;
+        format!("n{}", self.nodes)
+    }
+
+    // Returns the name of the node that carries the expression's value.
+    fn write_expression<W: Word>(&mut self, expression: &Expression<W>) -> String {
+        let (label, operands) = match *expression {
+            Register(ref register) => return format!("{:?}", register),
+            Literal(literal) => {
+                let node = self.new_node();
+                writeln!(self.output, "    {} [shape=plaintext, label=\"{}\"];", node, literal)
+                    .unwrap();
+                return node;
+            }
+            And(ref left, ref right) => ("AND", vec![left, right]),
+            Or(ref left, ref right) => ("OR", vec![left, right]),
+            LShift(ref left, ref right) => ("LSHIFT", vec![left, right]),
+            RShift(ref left, ref right) => ("RSHIFT", vec![left, right]),
+            Complement(ref expression) => ("NOT", vec![expression]),
+        };
+
+        let node = self.new_node();
+        writeln!(self.output, "    {} [shape=invhouse, label=\"{}\"];", node, label).unwrap();
+        for operand in operands {
+            let operand = self.write_expression(operand);
+            writeln!(self.output, "    {} -> {};", operand, node).unwrap();
+        }
+        node
+    }
+}
+
+impl<W: Word> CPU<W> {
+    pub fn to_dot(&self, cone_of: Option<&str>) -> String {
+        let mut wires = match cone_of {
+            Some(register) => self.get_cone(register),
+            None => {
+                let mut wires = self.registers.keys().cloned().collect::<HashSet<_>>();
+                for expression in self.registers.values() {
+                    wires.extend(expression.get_registers().into_iter().map(|r| r.to_owned()));
+                }
+                wires
+            }
+        }
+        .into_iter()
+        .collect::<Vec<_>>();
+        wires.sort();
+
+        let mut writer = DotWriter {
+            output: String::from("digraph circuit {\n    rankdir=LR;\n"),
+            nodes: 0,
+        };
+
+        for wire in &wires {
+            let (label, style) = match (self.overrides.get(wire), self.cache.get(wire)) {
+                (Some(value), _) => (format!("{} = {} (override)", wire, value), "bold"),
+                (None, Some(value)) => (format!("{} = {}", wire, value), "solid"),
+                (None, None) if !self.registers.contains_key(wire) => (wire.clone(), "dashed"),
+                (None, None) => (wire.clone(), "solid"),
+            };
+            writeln!(writer.output,
+                     "    {:?} [shape=box, style={}, label={:?}];",
+                     wire,
+                     style,
+                     label)
+                .unwrap();
+        }
+
+        for wire in &wires {
+            if let Some(expression) = self.registers.get(wire) {
+                let node = writer.write_expression(expression);
+                writeln!(writer.output, "    {} -> {:?};", node, wire).unwrap();
+            }
+        }
+
+        writer.output.push_str("}\n");
+        writer.output
+    }
+}
+
+#[cfg(test)]
+use Instruction;
+
+#[test]
+fn test_dot_export() {
+    let mut cpu = CPU::new();
+    cpu.insert(Instruction::parse("x AND y -> d"));
+    cpu.insert(Instruction::parse("123 -> x"));
+    cpu.insert(Instruction::parse("y -> z"));
+    cpu.insert(Instruction::parse("NOT z -> h"));
+    cpu.evaluate("x");
+
+    let dot = cpu.to_dot(None);
+    assert!(dot.starts_with("digraph circuit {"));
+    assert!(dot.contains("    \"x\" [shape=box, style=solid, label=\"x = 123\"];"));
+    assert!(dot.contains("    \"y\" [shape=box, style=dashed, label=\"y\"];"));
+    assert!(dot.contains("[shape=invhouse, label=\"AND\"]"));
+    assert!(dot.contains("[shape=invhouse, label=\"NOT\"]"));
+    assert!(dot.contains("[shape=plaintext, label=\"123\"]"));
+    assert!(dot.contains("    \"y\" -> \"z\";"));
+    assert!(dot.ends_with("}\n"));
+}
+
+#[test]
+fn test_dot_export_of_cone() {
+    let mut cpu = CPU::new();
+    cpu.insert(Instruction::parse("x AND y -> d"));
+    cpu.insert(Instruction::parse("d OR 1 -> e"));
+    cpu.insert(Instruction::parse("123 -> x"));
+    cpu.insert(Instruction::parse("456 -> y"));
+    cpu.insert(Instruction::parse("NOT x -> h"));
+    cpu.override_wire("y", 7);
+
+    let dot = cpu.to_dot(Some("e"));
+    assert!(dot.contains("label=\"y = 7 (override)\""));
+    assert!(dot.contains("\"d\" [shape=box"));
+    assert!(dot.contains("\"x\" [shape=box"));
+    assert!(!dot.contains("\"h\""));
+    assert!(!dot.contains("NOT"));
+}
@@ -1,67 +1,264 @@
-use std::collections::HashMap;
-use std::ops::Not;
+mod program;
+mod dot;
+mod optimize;
+mod solve;
+mod repl;
+mod parse;
+mod verilog;
+mod stats;
+
+use std::collections::{HashMap, HashSet};
+use std::ops::{BitAnd, BitOr, Not};
 use std::str::FromStr;
 use std::path::Path;
 use std::fs::File;
 use std::io::prelude::*;
+use std::io;
+use std::fmt;
+use std::env::args;
 
 use Expression::*;
+use EvaluationError::*;
+use program::Program;
+use repl::Repl;
+
+// Wires carry one of these, so values wrap at its width and shifting by that or more gives 0.
+trait Word: Copy + Default + PartialEq + fmt::Debug + fmt::Display + FromStr +
+            BitAnd<Output = Self> + BitOr<Output = Self> + Not<Output = Self>
+{
+    const BITS: u32;
+
+    fn shift_left(self, amount: Self) -> Self;
+    fn shift_right(self, amount: Self) -> Self;
+    fn to_u64(self) -> u64;
+    // Keeps the lowest bits of the value.
+    fn truncate(value: u64) -> Self;
+}
+
+macro_rules! impl_word {
+    ($($word:ident),*) => {
+        $(
+            impl Word for $word {
+                const BITS: u32 = $word::BITS;
+
+                fn shift_left(self, amount: Self) -> Self {
+                    if amount.to_u64() < Self::BITS as u64 { self << amount } else { 0 }
+                }
+
+                fn shift_right(self, amount: Self) -> Self {
+                    if amount.to_u64() < Self::BITS as u64 { self >> amount } else { 0 }
+                }
+
+                fn to_u64(self) -> u64 {
+                    self as u64
+                }
+
+                fn truncate(value: u64) -> Self {
+                    value as $word
+                }
+            }
+        )*
+    }
+}
 
-struct CPU {
-    registers: HashMap<String, Expression>,
-    cache: HashMap<String, Option<u16>>,
+impl_word!(u8, u16, u32, u64);
+
+struct CPU<W> {
+    registers: HashMap<String, Expression<W>>,
+    cache: HashMap<String, W>,
+    overrides: HashMap<String, W>,
+    users: HashMap<String, HashSet<String>>,
 }
 
 #[derive(Debug, PartialEq, Clone)]
-enum Expression {
+enum Expression<W> {
     Register(String),
-    Literal(u16),
-    And(Box<Expression>, Box<Expression>),
-    Or(Box<Expression>, Box<Expression>),
-    LShift(Box<Expression>, Box<Expression>),
-    RShift(Box<Expression>, Box<Expression>),
-    Complement(Box<Expression>),
+    Literal(W),
+    And(Box<Expression<W>>, Box<Expression<W>>),
+    Or(Box<Expression<W>>, Box<Expression<W>>),
+    LShift(Box<Expression<W>>, Box<Expression<W>>),
+    RShift(Box<Expression<W>>, Box<Expression<W>>),
+    Complement(Box<Expression<W>>),
+}
+
+// Both variants carry the chain of wires that was followed from the
+// evaluated wire to the point where evaluation got stuck.
+#[derive(Debug, PartialEq)]
+enum EvaluationError {
+    Cycle(Vec<String>),
+    Undriven(Vec<String>),
+}
+
+#[derive(Debug, PartialEq)]
+struct Diagnostics {
+    undriven: Vec<String>,
+    unreachable: Vec<String>,
 }
 
 #[derive(Debug, PartialEq)]
-struct Instruction {
+struct Instruction<W> {
     target_register: String,
-    expression: Expression,
+    expression: Expression<W>,
 }
 
-impl CPU {
+impl<W: Word> CPU<W> {
     fn new() -> Self {
         CPU {
             registers: HashMap::new(),
             cache: HashMap::new(),
+            overrides: HashMap::new(),
+            users: HashMap::new(),
         }
     }
 
-    fn insert(&mut self, instruction: Instruction) {
-        self.registers.insert(instruction.target_register, instruction.expression);
+    fn insert(&mut self, instruction: Instruction<W>) {
+        let register = instruction.target_register;
+        for dependency in instruction.expression.get_registers() {
+            self.users.entry(dependency.to_owned()).or_default().insert(register.clone());
+        }
+        if let Some(previous) = self.registers.insert(register.clone(), instruction.expression) {
+            for dependency in previous.get_registers() {
+                if !self.registers[&register].get_registers().contains(&dependency) {
+                    self.users.get_mut(dependency).map(|users| users.remove(&register));
+                }
+            }
+        }
+        self.invalidate(&register);
+    }
+
+    fn override_wire(&mut self, register: &str, value: W) {
+        self.overrides.insert(register.to_owned(), value);
+        self.invalidate(register);
+    }
+
+    fn remove_override(&mut self, register: &str) {
+        if self.overrides.remove(register).is_some() {
+            self.invalidate(register);
+        }
+    }
+
+    // Forgets the cached value of the register and of everything that is
+    // computed from it, so they get recomputed the next time they're needed.
+    fn invalidate(&mut self, register: &str) {
+        let mut visited = HashSet::new();
+        let mut queue = vec![register.to_owned()];
+        while let Some(register) = queue.pop() {
+            self.cache.remove(&register);
+            if let Some(users) = self.users.get(&register) {
+                queue.extend(users.iter().filter(|u| !visited.contains(*u)).cloned());
+            }
+            visited.insert(register);
+        }
+    }
+
+    fn evaluate(&mut self, register: &str) -> Option<W> {
+        self.try_evaluate(register).ok()
+    }
+
+    fn try_evaluate(&mut self, register: &str) -> Result<W, EvaluationError> {
+        self.evaluate_inner(register, &mut Vec::new())
+    }
+
+    fn evaluate_inner(&mut self,
+                      register: &str,
+                      path: &mut Vec<String>)
+                      -> Result<W, EvaluationError> {
+        if let Some(&value) = self.overrides.get(register).or_else(|| self.cache.get(register)) {
+            return Ok(value);
+        }
+
+        let chain = |path: &Vec<String>| {
+            let mut chain = path.clone();
+            chain.push(register.to_owned());
+            chain
+        };
+        if path.iter().any(|r| r == register) {
+            return Err(Cycle(chain(path)));
+        }
+        let expression = match self.registers.get(register) {
+            Some(expression) => expression.clone(),
+            None => return Err(Undriven(chain(path))),
+        };
+
+        path.push(register.to_owned());
+        let result = expression.evaluate(self, path);
+        path.pop();
+
+        if let Ok(value) = result {
+            self.cache.insert(register.to_owned(), value);
+        }
+        result
     }
 
-    fn clear_cache(&mut self) {
-        self.cache.clear();
+    // All the wires the given wire is computed from, including itself.
+    fn get_cone(&self, register: &str) -> HashSet<String> {
+        let mut cone = HashSet::new();
+        let mut queue = vec![register.to_owned()];
+        while let Some(register) = queue.pop() {
+            if let Some(expression) = self.registers.get(&register) {
+                queue.extend(expression.get_registers()
+                                       .into_iter()
+                                       .filter(|r| !cone.contains(*r))
+                                       .map(|r| r.to_owned()));
+            }
+            cone.insert(register);
+        }
+        cone
     }
 
-    fn set_cache_value(&mut self, register: &str, value: Option<u16>) {
-        self.cache.insert(register.to_owned(), value);
+    // The wires that no other wire reads from.
+    fn get_sinks(&self) -> Vec<&str> {
+        let mut sinks = self.registers
+                            .keys()
+                            .filter(|r| self.users.get(*r).is_none_or(|users| users.is_empty()))
+                            .map(|r| r.as_str())
+                            .collect::<Vec<_>>();
+        sinks.sort();
+        sinks
     }
 
-    fn evaluate(&mut self, register: &str) -> Option<u16> {
-        if self.cache.contains_key(register) {
-            self.cache.get(register).cloned().unwrap_or(None)
-        } else {
-            self.set_cache_value(register, None);
-            let result = self.registers.get(register).cloned().and_then(|r| r.evaluate(self));
-            self.set_cache_value(register, result);
-            result
+    // Resolves wires in dependency order until nothing changes anymore, so
+    // whatever is left over depends on a cycle or on an undriven wire.
+    fn diagnose(&self) -> Diagnostics {
+        let mut undriven = HashSet::new();
+        for expression in self.registers.values() {
+            for register in expression.get_registers() {
+                if !self.registers.contains_key(register) {
+                    undriven.insert(register.to_owned());
+                }
+            }
+        }
+
+        let mut resolved = HashSet::new();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (register, expression) in &self.registers {
+                if !resolved.contains(register) &&
+                   expression.get_registers().iter().all(|r| resolved.contains(*r)) {
+                    resolved.insert(register.clone());
+                    changed = true;
+                }
+            }
+        }
+
+        let mut undriven = undriven.into_iter().collect::<Vec<_>>();
+        undriven.sort();
+        let mut unreachable = self.registers
+                                  .keys()
+                                  .filter(|r| !resolved.contains(*r))
+                                  .cloned()
+                                  .collect::<Vec<_>>();
+        unreachable.sort();
+
+        Diagnostics {
+            undriven,
+            unreachable,
         }
     }
 
     fn insert_all<'a, I>(&mut self, instructions: I)
-        where I: IntoIterator<Item = Instruction>
+        where I: IntoIterator<Item = Instruction<W>>
     {
         for instruction in instructions {
             self.insert(instruction);
@@ -69,75 +266,93 @@ impl CPU {
     }
 }
 
-impl Expression {
-    fn parse(line: &str) -> Expression {
-        if let Some(index) = line.find(" AND ") {
-            let split = line.split_at(index);
-            let left = Self::parse(split.0);
-            let right = Self::parse(&split.1[5..]);
-            And(Box::new(left), Box::new(right))
-        } else if let Some(index) = line.find(" OR ") {
-            let split = line.split_at(index);
-            let left = Self::parse(split.0);
-            let right = Self::parse(&split.1[4..]);
-            Or(Box::new(left), Box::new(right))
-        } else if let Some(index) = line.find(" LSHIFT ") {
-            let split = line.split_at(index);
-            let left = Self::parse(split.0);
-            let right = Self::parse(&split.1[8..]);
-            LShift(Box::new(left), Box::new(right))
-        } else if let Some(index) = line.find(" RSHIFT ") {
-            let split = line.split_at(index);
-            let left = Self::parse(split.0);
-            let right = Self::parse(&split.1[8..]);
-            RShift(Box::new(left), Box::new(right))
-        } else if line.starts_with("NOT ") {
-            let inner = Self::parse(&line[4..]);
-            Complement(Box::new(inner))
-        } else if let Ok(literal) = u16::from_str(line) {
-            Literal(literal)
-        } else {
-            Register(line.to_owned())
-        }
-    }
-
-    fn evaluate(&self, cpu: &mut CPU) -> Option<u16> {
+impl<W: Word> Expression<W> {
+    fn evaluate(&self, cpu: &mut CPU<W>, path: &mut Vec<String>) -> Result<W, EvaluationError> {
         match self {
-            &Register(ref register) => cpu.evaluate(&register),
-            &Literal(literal) => Some(literal),
+            &Register(ref register) => cpu.evaluate_inner(register, path),
+            &Literal(literal) => Ok(literal),
             &And(ref left, ref right) => {
-                left.evaluate(cpu).and_then(|l| right.evaluate(cpu).map(|r| l & r))
+                left.evaluate(cpu, path).and_then(|l| right.evaluate(cpu, path).map(|r| l & r))
             }
             &Or(ref left, ref right) => {
-                left.evaluate(cpu).and_then(|l| right.evaluate(cpu).map(|r| l | r))
+                left.evaluate(cpu, path).and_then(|l| right.evaluate(cpu, path).map(|r| l | r))
             }
             &LShift(ref left, ref right) => {
-                left.evaluate(cpu)
-                    .and_then(|l| right.evaluate(cpu).map(|r| l.wrapping_shl(r as u32)))
+                left.evaluate(cpu, path)
+                    .and_then(|l| right.evaluate(cpu, path).map(|r| l.shift_left(r)))
             }
             &RShift(ref left, ref right) => {
-                left.evaluate(cpu)
-                    .and_then(|l| right.evaluate(cpu).map(|r| l.wrapping_shr(r as u32)))
+                left.evaluate(cpu, path)
+                    .and_then(|l| right.evaluate(cpu, path).map(|r| l.shift_right(r)))
+            }
+            &Complement(ref expression) => expression.evaluate(cpu, path).map(|v| !v),
+        }
+    }
+
+    fn get_registers(&self) -> Vec<&str> {
+        match *self {
+            Register(ref register) => vec![register],
+            Literal(_) => Vec::new(),
+            And(ref left, ref right) |
+            Or(ref left, ref right) |
+            LShift(ref left, ref right) |
+            RShift(ref left, ref right) => {
+                let mut registers = left.get_registers();
+                registers.extend(right.get_registers());
+                registers
             }
-            &Complement(ref expression) => expression.evaluate(cpu).map(|v| v.not()),
+            Complement(ref expression) => expression.get_registers(),
         }
     }
 }
 
-impl Instruction {
-    fn new(register: &str, expression: Expression) -> Self {
+impl fmt::Display for EvaluationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Cycle(ref chain) => write!(f, "Dependency cycle {}", chain.join(" -> ")),
+            Undriven(ref chain) => {
+                write!(f,
+                       "Wire '{}' is never driven, needed by {}",
+                       chain.last().unwrap(),
+                       chain.join(" -> "))
+            }
+        }
+    }
+}
+
+impl<W: Word> fmt::Display for Expression<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Register(ref register) => write!(f, "{}", register),
+            Literal(literal) => write!(f, "{}", literal),
+            And(ref left, ref right) => write!(f, "{} AND {}", left, right),
+            Or(ref left, ref right) => write!(f, "{} OR {}", left, right),
+            LShift(ref left, ref right) => write!(f, "{} LSHIFT {}", left, right),
+            RShift(ref left, ref right) => write!(f, "{} RSHIFT {}", left, right),
+            Complement(ref expression) => write!(f, "NOT {}", expression),
+        }
+    }
+}
+
+impl<W: Word> fmt::Display for Instruction<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} -> {}", self.expression, self.target_register)
+    }
+}
+
+impl<W> Instruction<W> {
+    fn new(register: &str, expression: Expression<W>) -> Self {
         Instruction {
             target_register: register.to_owned(),
             expression: expression,
         }
     }
+}
 
+#[cfg(test)]
+impl Instruction<u16> {
     fn parse(line: &str) -> Self {
-        let index = line.find(" -> ").unwrap();
-        let splits = line.split_at(index);
-        let expression = Expression::parse(splits.0);
-        let register = &splits.1[4..];
-        Instruction::new(register, expression)
+        Instruction::try_parse(line).unwrap()
     }
 }
 
@@ -148,30 +363,187 @@ fn read_file(path: &Path) -> Vec<String> {
     input.lines().into_iter().map(|s| s.to_owned()).collect()
 }
 
-fn parse_instructions<'a, I>(lines: I) -> Vec<Instruction>
-    where I: IntoIterator<Item = &'a String>
+fn parse_instructions<'a, I, W>(lines: I) -> Result<Vec<Instruction<W>>, String>
+    where I: IntoIterator<Item = &'a String>,
+          W: Word
 {
-    lines.into_iter().map(|l| Instruction::parse(l)).collect()
+    lines.into_iter()
+         .enumerate()
+         .map(|(i, l)| Instruction::try_parse(l).map_err(|e| format!("Line {}: {}", i + 1, e)))
+         .collect()
 }
 
 fn main() {
+    let arguments = args().skip(1).collect::<Vec<_>>();
+    let width = match arguments.iter().position(|a| a == "--width") {
+        Some(index) => arguments.get(index + 1).map_or("", |w| w.as_str()),
+        None => "16",
+    };
+    match width {
+        "8" => run::<u8>(&arguments),
+        "16" => run::<u16>(&arguments),
+        "32" => run::<u32>(&arguments),
+        "64" => run::<u64>(&arguments),
+        _ => println!("--width has to be one of 8, 16, 32 or 64"),
+    }
+}
+
+fn run<W: Word>(arguments: &[String]) {
     let lines = read_file(Path::new("input.txt"));
-    let instructions = parse_instructions(&lines);
-    let mut cpu = CPU::new();
+    let instructions = match parse_instructions(&lines) {
+        Ok(instructions) => instructions,
+        Err(err) => {
+            println!("{}", err);
+            return;
+        }
+    };
+    let mut cpu = CPU::<W>::new();
 
     cpu.insert_all(instructions);
-    let value_of_a = cpu.evaluate("a");
 
-    println!("Value of Register 'a' for Part 1: {}",
-             value_of_a.map(|x| x.to_string()).unwrap_or("-".to_owned()));
+    if arguments.iter().any(|a| a == "--repl") {
+        let stdin = io::stdin();
+        Repl::new(&lines, cpu).run(stdin.lock(), io::stdout()).unwrap();
+        return;
+    }
 
-    cpu.clear_cache();
-    cpu.set_cache_value("b", value_of_a);
+    if let Some(index) = arguments.iter().position(|a| a == "--dot") {
+        let cone_of = arguments.get(index + 1).filter(|a| !a.starts_with("--") && !a.contains('='));
+        let _ = cpu.try_evaluate(cone_of.map_or("a", |r| r));
+        print!("{}", cpu.to_dot(cone_of.map(|r| r.as_str())));
+        return;
+    }
+
+    if let Some(index) = arguments.iter().position(|a| a == "--optimize") {
+        let output = arguments.get(index + 1).filter(|a| !a.starts_with("--") && !a.contains('='));
+        match cpu.optimize(&[output.map_or("a", |r| r)]) {
+            Ok(instructions) => {
+                for instruction in instructions {
+                    println!("{}", instruction);
+                }
+            }
+            Err(err) => println!("Netlist can't be optimized: {}", err),
+        }
+        return;
+    }
+
+    if let Some(index) = arguments.iter().position(|a| a == "--solve") {
+        let free = arguments.get(index + 1).map(|w| w.split(',').collect::<Vec<_>>());
+        let target = arguments.get(index + 2).and_then(|a| parse_assignment(a));
+        match (free, target) {
+            (Some(free), Some((output, target))) => solve(&cpu, &free, &output, target),
+            _ => println!("Usage: --solve <wire>[,<wire>...] <wire>=<value>"),
+        }
+        return;
+    }
+
+    if let Some(index) = arguments.iter().position(|a| a == "--verilog") {
+        let outputs = match arguments.get(index + 1).filter(|a| !a.starts_with("--")) {
+            Some(outputs) => outputs.split(',').collect::<Vec<_>>(),
+            None => cpu.get_sinks(),
+        };
+        match cpu.to_verilog(&outputs) {
+            Ok(verilog) => print!("{}", verilog),
+            Err(err) => println!("Netlist can't be exported: {}", err),
+        }
+        return;
+    }
+
+    if arguments.iter().any(|a| a == "--stats") {
+        match cpu.stats() {
+            Ok(stats) => print!("{}", stats),
+            Err(err) => println!("Netlist can't be analyzed: {}", err),
+        }
+        return;
+    }
+
+    if arguments.iter().any(|a| a == "--compiled") {
+        run_compiled(&mut cpu);
+        return;
+    }
+
+    let value_of_a = match cpu.try_evaluate("a") {
+        Ok(value) => value,
+        Err(err) => {
+            println!("Register 'a' can't be evaluated: {}", err);
+            print_diagnostics(&cpu.diagnose());
+            return;
+        }
+    };
+
+    println!("Value of Register 'a' for Part 1: {}", value_of_a);
+
+    cpu.override_wire("b", value_of_a);
 
     let new_value_of_a = cpu.evaluate("a");
 
     println!("Value of Register 'a' for Part 2: {}",
              new_value_of_a.map(|x| x.to_string()).unwrap_or("-".to_owned()));
+    cpu.remove_override("b");
+
+    for (register, value) in arguments.iter().filter_map(|a| parse_assignment::<W>(a)) {
+        cpu.override_wire(&register, value);
+        match cpu.try_evaluate("a") {
+            Ok(value_of_a) => {
+                println!("Value of Register 'a' if {} = {}: {}", register, value, value_of_a)
+            }
+            Err(err) => {
+                println!("Register 'a' can't be evaluated if {} = {}: {}", register, value, err)
+            }
+        }
+        cpu.remove_override(&register);
+    }
+}
+
+fn parse_assignment<W: Word>(argument: &str) -> Option<(String, W)> {
+    let index = argument.find('=')?;
+    let value = W::from_str(&argument[index + 1..]).ok()?;
+    Some((argument[..index].to_owned(), value))
+}
+
+fn run_compiled<W: Word>(cpu: &mut CPU<W>) {
+    let value_of_a = match Program::compile(cpu, &["a"]) {
+        Ok(program) => program.evaluate("a").unwrap(),
+        Err(err) => {
+            println!("Register 'a' can't be compiled: {}", err);
+            print_diagnostics(&cpu.diagnose());
+            return;
+        }
+    };
+
+    println!("Value of Register 'a' for Part 1: {}", value_of_a);
+
+    cpu.insert(Instruction::new("b", Literal(value_of_a)));
+    let program = Program::compile(cpu, &["a"]).unwrap();
+
+    println!("Value of Register 'a' for Part 2: {}", program.evaluate("a").unwrap());
+}
+
+fn solve<W: Word>(cpu: &CPU<W>, free: &[&str], output: &str, target: W) {
+    match cpu.solve(free, output, target) {
+        Ok(ref solutions) if solutions.is_empty() => {
+            println!("No assignment of {} makes {} = {}", free.join(", "), output, target)
+        }
+        Ok(solutions) => {
+            for solution in solutions {
+                let assignment = free.iter()
+                                     .zip(solution)
+                                     .map(|(register, value)| format!("{} = {}", register, value))
+                                     .collect::<Vec<_>>();
+                println!("{}", assignment.join(", "));
+            }
+        }
+        Err(err) => println!("Can't solve for {} = {}: {}", output, target, err),
+    }
+}
+
+fn print_diagnostics(diagnostics: &Diagnostics) {
+    if !diagnostics.undriven.is_empty() {
+        println!("Undriven wires: {}", diagnostics.undriven.join(", "));
+    }
+    if !diagnostics.unreachable.is_empty() {
+        println!("Unreachable wires: {}", diagnostics.unreachable.join(", "));
+    }
 }
 
 #[test]
@@ -230,3 +602,117 @@ fn test_loop_handling() {
     cpu.insert(Instruction::parse("456 -> y"));
     assert_eq!(cpu.evaluate("a"), None);
 }
+
+#[test]
+fn test_cycle_diagnostics() {
+    let mut cpu = CPU::new();
+    cpu.insert(Instruction::parse("5 AND x -> a"));
+    cpu.insert(Instruction::parse("NOT x -> b"));
+    cpu.insert(Instruction::parse("y AND b -> x"));
+    cpu.insert(Instruction::parse("456 -> y"));
+    cpu.insert(Instruction::parse("y OR 1 -> c"));
+    assert_eq!(cpu.try_evaluate("a"), Err(Cycle(vec!["a".to_owned(), "x".to_owned(),
+                                                     "b".to_owned(), "x".to_owned()])));
+    assert_eq!(cpu.try_evaluate("c"), Ok(457));
+    assert_eq!(cpu.diagnose(),
+               Diagnostics {
+                   undriven: vec![],
+                   unreachable: vec!["a".to_owned(), "b".to_owned(), "x".to_owned()],
+               });
+}
+
+#[test]
+fn test_undriven_diagnostics() {
+    let mut cpu = CPU::new();
+    cpu.insert(Instruction::parse("b OR c -> a"));
+    cpu.insert(Instruction::parse("1 -> b"));
+    cpu.insert(Instruction::parse("NOT z -> c"));
+    cpu.insert(Instruction::parse("w AND b -> d"));
+    assert_eq!(cpu.try_evaluate("a"),
+               Err(Undriven(vec!["a".to_owned(), "c".to_owned(), "z".to_owned()])));
+    assert_eq!(cpu.try_evaluate("a").unwrap_err().to_string(),
+               "Wire 'z' is never driven, needed by a -> c -> z");
+    assert_eq!(cpu.diagnose(),
+               Diagnostics {
+                   undriven: vec!["w".to_owned(), "z".to_owned()],
+                   unreachable: vec!["a".to_owned(), "c".to_owned(), "d".to_owned()],
+               });
+}
+
+#[test]
+fn test_overrides() {
+    let mut cpu = CPU::new();
+    cpu.insert(Instruction::parse("123 -> x"));
+    cpu.insert(Instruction::parse("456 -> y"));
+    cpu.insert(Instruction::parse("x AND y -> d"));
+    cpu.insert(Instruction::parse("d OR 1 -> e"));
+    cpu.insert(Instruction::parse("NOT y -> i"));
+    assert_eq!(cpu.evaluate("e"), Some(73));
+    assert_eq!(cpu.evaluate("i"), Some(65079));
+
+    cpu.override_wire("x", 0xFFFF);
+    assert!(!cpu.cache.contains_key("d"));
+    assert!(!cpu.cache.contains_key("e"));
+    assert_eq!(cpu.cache.get("y"), Some(&456));
+    assert_eq!(cpu.cache.get("i"), Some(&65079));
+    assert_eq!(cpu.evaluate("e"), Some(457));
+
+    cpu.override_wire("d", 6);
+    assert_eq!(cpu.evaluate("e"), Some(7));
+    assert_eq!(cpu.evaluate("x"), Some(0xFFFF));
+
+    cpu.remove_override("d");
+    assert_eq!(cpu.evaluate("e"), Some(457));
+    cpu.remove_override("x");
+    assert_eq!(cpu.evaluate("e"), Some(73));
+}
+
+#[test]
+fn test_width() {
+    assert_eq!(Instruction::<u16>::try_parse("65536 -> x").unwrap_err().to_string(),
+               "Literal 65536 doesn't fit into 16 bits at column 1");
+    assert_eq!(Instruction::<u8>::try_parse("x AND 256 -> y").unwrap_err().to_string(),
+               "Literal 256 doesn't fit into 8 bits at column 7");
+    assert_eq!(Instruction::try_parse("x AND 4294967296 -> y"),
+               Ok(Instruction::new("y",
+                                   And(Box::new(Register("x".to_owned())),
+                                       Box::new(Literal(1u64 << 32))))));
+    assert_eq!(parse_assignment::<u8>("b=256"), None);
+    assert_eq!(parse_assignment::<u32>("b=65536"), Some(("b".to_owned(), 65536)));
+
+    let mut cpu = CPU::<u8>::new();
+    cpu.insert(Instruction::try_parse("123 -> x").unwrap());
+    cpu.insert(Instruction::try_parse("NOT x -> h").unwrap());
+    cpu.insert(Instruction::try_parse("x LSHIFT 2 -> f").unwrap());
+    cpu.insert(Instruction::try_parse("x LSHIFT 8 -> g").unwrap());
+    cpu.insert(Instruction::try_parse("x RSHIFT 9 -> i").unwrap());
+    assert_eq!(cpu.evaluate("h"), Some(132));
+    assert_eq!(cpu.evaluate("f"), Some(236));
+    assert_eq!(cpu.evaluate("g"), Some(0));
+    assert_eq!(cpu.evaluate("i"), Some(0));
+
+    let mut cpu = CPU::<u64>::new();
+    cpu.insert(Instruction::try_parse("1 LSHIFT 40 -> x").unwrap());
+    cpu.insert(Instruction::try_parse("NOT x -> y").unwrap());
+    cpu.insert(Instruction::try_parse("x LSHIFT 64 -> z").unwrap());
+    assert_eq!(cpu.evaluate("x"), Some(1 << 40));
+    assert_eq!(cpu.evaluate("y"), Some(!(1 << 40)));
+    assert_eq!(cpu.evaluate("z"), Some(0));
+    assert_eq!(Program::compile(&cpu, &["y"]).unwrap().evaluate("y"), Some(!(1 << 40)));
+}
+
+#[test]
+fn test_insert_invalidates_users() {
+    let mut cpu = CPU::new();
+    cpu.insert(Instruction::parse("1 -> x"));
+    cpu.insert(Instruction::parse("x LSHIFT 2 -> f"));
+    assert_eq!(cpu.evaluate("f"), Some(4));
+
+    cpu.insert(Instruction::parse("3 -> x"));
+    assert_eq!(cpu.evaluate("f"), Some(12));
+
+    cpu.insert(Instruction::parse("2 -> y"));
+    cpu.insert(Instruction::parse("y LSHIFT 2 -> f"));
+    assert_eq!(cpu.evaluate("f"), Some(8));
+    assert!(!cpu.users["x"].contains("f"));
+}
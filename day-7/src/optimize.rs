@@ -0,0 +1,177 @@
+use std::collections::{HashMap, HashSet};
+
+use {CPU, Expression, EvaluationError, Instruction, Word};
+use Expression::*;
+
+struct Optimizer<'a, W> {
+    simplified: HashMap<&'a str, Expression<W>>,
+}
+
+fn is_leaf<W>(expression: &Expression<W>) -> bool {
+    matches!(*expression, Register(_) | Literal(_))
+}
+
+impl<'a, W: Word> Optimizer<'a, W> {
+    // Wires that turned out to be constant or to just forward another wire
+    // get replaced by what they carry.
+    fn resolve(&self, register: &str) -> Expression<W> {
+        match self.simplified.get(register) {
+            Some(expression) if is_leaf(expression) => expression.clone(),
+            _ => Register(register.to_owned()),
+        }
+    }
+
+    fn simplify(&self, expression: &Expression<W>) -> Expression<W> {
+        let (zero, mask) = (W::default(), !W::default());
+        let shifts_out = |amount: W| amount.to_u64() >= W::BITS as u64;
+        match *expression {
+            Register(ref register) => self.resolve(register),
+            Literal(literal) => Literal(literal),
+            And(ref left, ref right) => {
+                match (self.simplify(left), self.simplify(right)) {
+                    (Literal(l), Literal(r)) => Literal(l & r),
+                    (Literal(l), _) | (_, Literal(l)) if l == zero => Literal(zero),
+                    (Literal(l), e) | (e, Literal(l)) if l == mask => e,
+                    (l, r) => if l == r { l } else { And(Box::new(l), Box::new(r)) },
+                }
+            }
+            Or(ref left, ref right) => {
+                match (self.simplify(left), self.simplify(right)) {
+                    (Literal(l), Literal(r)) => Literal(l | r),
+                    (Literal(l), _) | (_, Literal(l)) if l == mask => Literal(mask),
+                    (Literal(l), e) | (e, Literal(l)) if l == zero => e,
+                    (l, r) => if l == r { l } else { Or(Box::new(l), Box::new(r)) },
+                }
+            }
+            LShift(ref left, ref right) => {
+                match (self.simplify(left), self.simplify(right)) {
+                    (Literal(l), Literal(r)) => Literal(l.shift_left(r)),
+                    (Literal(l), _) if l == zero => Literal(zero),
+                    (e, Literal(r)) if r == zero => e,
+                    (_, Literal(r)) if shifts_out(r) => Literal(zero),
+                    (l, r) => LShift(Box::new(l), Box::new(r)),
+                }
+            }
+            RShift(ref left, ref right) => {
+                match (self.simplify(left), self.simplify(right)) {
+                    (Literal(l), Literal(r)) => Literal(l.shift_right(r)),
+                    (Literal(l), _) if l == zero => Literal(zero),
+                    (e, Literal(r)) if r == zero => e,
+                    (_, Literal(r)) if shifts_out(r) => Literal(zero),
+                    (l, r) => RShift(Box::new(l), Box::new(r)),
+                }
+            }
+            Complement(ref expression) => {
+                match self.simplify(expression) {
+                    Literal(literal) => Literal(!literal),
+                    Complement(inner) => *inner,
+                    Register(register) => {
+                        match self.simplified.get(register.as_str()) {
+                            Some(Complement(inner)) if is_leaf(inner) => (**inner).clone(),
+                            _ => Complement(Box::new(Register(register))),
+                        }
+                    }
+                    e => Complement(Box::new(e)),
+                }
+            }
+        }
+    }
+}
+
+impl<W: Word> CPU<W> {
+    // Folds constants and trivial gates and drops every wire that doesn't
+    // feed one of the outputs. Undriven wires are kept as inputs.
+    pub fn optimize(&self, outputs: &[&str]) -> Result<Vec<Instruction<W>>, EvaluationError> {
+        let order = self.sort_topologically(outputs, true)?;
+
+        let mut optimizer = Optimizer {
+            simplified: HashMap::new(),
+        };
+        for &register in &order {
+            let expression = optimizer.simplify(&self.registers[register]);
+            optimizer.simplified.insert(register, expression);
+        }
+
+        let mut live = HashSet::new();
+        let mut queue = outputs.to_vec();
+        while let Some(register) = queue.pop() {
+            if live.insert(register) {
+                if let Some(expression) = optimizer.simplified.get(register) {
+                    queue.extend(expression.get_registers());
+                }
+            }
+        }
+
+        Ok(order.into_iter()
+                .filter(|r| live.contains(r))
+                .map(|r| Instruction::new(r, optimizer.simplified[r].clone()))
+                .collect())
+    }
+}
+
+#[cfg(test)]
+fn optimize_lines(lines: &[&str], outputs: &[&str]) -> Vec<String> {
+    let mut cpu = CPU::new();
+    for line in lines {
+        cpu.insert(Instruction::parse(line));
+    }
+    cpu.optimize(outputs).unwrap().iter().map(|i| i.to_string()).collect()
+}
+
+#[test]
+fn test_constant_folding() {
+    assert_eq!(optimize_lines(&["123 -> x", "456 -> y", "x AND y -> d", "NOT d -> a"], &["a"]),
+               vec!["65463 -> a"]);
+    assert_eq!(optimize_lines(&["1 LSHIFT 3 -> s", "s OR z -> a"], &["a"]),
+               vec!["8 OR z -> a"]);
+}
+
+#[test]
+fn test_identities() {
+    assert_eq!(optimize_lines(&["x AND 65535 -> a"], &["a"]), vec!["x -> a"]);
+    assert_eq!(optimize_lines(&["65535 AND x -> b", "b OR 0 -> a"], &["a"]), vec!["x -> a"]);
+    assert_eq!(optimize_lines(&["x LSHIFT 0 -> b", "b OR y -> a"], &["a"]),
+               vec!["x OR y -> a"]);
+    assert_eq!(optimize_lines(&["x RSHIFT 16 -> b", "b OR y -> a"], &["a"]),
+               vec!["y -> a"]);
+    assert_eq!(optimize_lines(&["NOT x -> b", "NOT b -> c", "c AND y -> a"], &["a"]),
+               vec!["x AND y -> a"]);
+    assert_eq!(optimize_lines(&["x AND 0 -> b", "b OR y -> a"], &["a"]), vec!["y -> a"]);
+    assert_eq!(optimize_lines(&["x OR x -> b", "b AND x -> a"], &["a"]), vec!["x -> a"]);
+}
+
+#[test]
+fn test_dead_wire_elimination() {
+    let lines = ["x AND y -> d", "d OR z -> e", "NOT x -> h", "h -> i", "d LSHIFT 2 -> f"];
+    assert_eq!(optimize_lines(&lines, &["e"]), vec!["x AND y -> d", "d OR z -> e"]);
+    assert_eq!(optimize_lines(&lines, &["i", "f"]),
+               vec!["NOT x -> h", "h -> i", "x AND y -> d", "d LSHIFT 2 -> f"]);
+}
+
+#[test]
+fn test_optimized_netlist_round_trips() {
+    use std::path::Path;
+    use read_file;
+
+    let lines = read_file(Path::new("input.txt"));
+    let mut cpu = CPU::new();
+    for line in lines.iter().filter(|l| !l.ends_with(" -> b")) {
+        cpu.insert(Instruction::parse(line));
+    }
+
+    let optimized = cpu.optimize(&["a"]).unwrap();
+    assert!(optimized.len() < cpu.registers.len());
+
+    let mut optimized_cpu = CPU::new();
+    for instruction in &optimized {
+        let reparsed = Instruction::parse(&instruction.to_string());
+        assert_eq!(&reparsed, instruction);
+        optimized_cpu.insert(reparsed);
+    }
+
+    for &b in &[0, 1, 3176, 44430, 65535] {
+        cpu.override_wire("b", b);
+        optimized_cpu.override_wire("b", b);
+        assert_eq!(optimized_cpu.evaluate("a"), cpu.evaluate("a"));
+    }
+}
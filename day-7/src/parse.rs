@@ -0,0 +1,272 @@
+use std::fmt;
+
+use {Expression, Instruction, Word};
+use Expression::*;
+
+#[derive(Debug, PartialEq)]
+pub enum ParseErrorKind {
+    UnexpectedCharacter(char),
+    InvalidLiteral(String),
+    LiteralTooWide(String, u32),
+    UnknownOperator(String),
+    ChainedOperator(String),
+    Expected(&'static str, String),
+}
+
+// Columns start at 1 and count bytes, which is the same as characters for
+// any line that made it through the tokenizer.
+#[derive(Debug, PartialEq)]
+pub struct ParseError {
+    column: usize,
+    kind: ParseErrorKind,
+}
+
+type BinaryOperator<W> = fn(Box<Expression<W>>, Box<Expression<W>>) -> Expression<W>;
+
+enum TokenKind<W> {
+    Wire,
+    Literal,
+    Not,
+    Binary(BinaryOperator<W>),
+    Arrow,
+    End,
+}
+
+struct Token<'a, W> {
+    column: usize,
+    text: &'a str,
+    kind: TokenKind<W>,
+}
+
+impl<'a, W> Token<'a, W> {
+    fn describe(&self) -> String {
+        match self.kind {
+            TokenKind::End => "the end of the line".to_owned(),
+            _ => format!("'{}'", self.text),
+        }
+    }
+
+    fn error(&self, kind: ParseErrorKind) -> ParseError {
+        ParseError {
+            column: self.column,
+            kind,
+        }
+    }
+}
+
+fn tokenize<'a, W>(line: &'a str) -> Result<Vec<Token<'a, W>>, ParseError> {
+    let bytes = line.as_bytes();
+    let mut tokens = Vec::new();
+    let mut index = 0;
+
+    while index < bytes.len() {
+        let start = index;
+        let c = bytes[index];
+        if c == b' ' || c == b'\t' {
+            index += 1;
+            continue;
+        }
+
+        let kind = if c == b'-' && bytes.get(index + 1) == Some(&b'>') {
+            index += 2;
+            TokenKind::Arrow
+        } else if c.is_ascii_alphanumeric() || c == b'_' {
+            while bytes.get(index).is_some_and(|b| b.is_ascii_alphanumeric() || *b == b'_') {
+                index += 1;
+            }
+            let word = &line[start..index];
+            let error = |kind| Err(ParseError { column: start + 1, kind });
+            if c.is_ascii_digit() {
+                if !word.bytes().all(|b| b.is_ascii_digit()) {
+                    return error(ParseErrorKind::InvalidLiteral(word.to_owned()));
+                }
+                TokenKind::Literal
+            } else if word.bytes().all(|b| b.is_ascii_uppercase()) {
+                match word {
+                    "NOT" => TokenKind::Not,
+                    "AND" => TokenKind::Binary(And),
+                    "OR" => TokenKind::Binary(Or),
+                    "LSHIFT" => TokenKind::Binary(LShift),
+                    "RSHIFT" => TokenKind::Binary(RShift),
+                    _ => return error(ParseErrorKind::UnknownOperator(word.to_owned())),
+                }
+            } else {
+                TokenKind::Wire
+            }
+        } else {
+            let c = line[start..].chars().next().unwrap();
+            return Err(ParseError {
+                column: start + 1,
+                kind: ParseErrorKind::UnexpectedCharacter(c),
+            });
+        };
+
+        tokens.push(Token {
+            column: start + 1,
+            text: &line[start..index],
+            kind,
+        });
+    }
+
+    tokens.push(Token {
+        column: line.len() + 1,
+        text: "",
+        kind: TokenKind::End,
+    });
+    Ok(tokens)
+}
+
+// instruction = expression "->" wire
+// expression  = unary [operator unary]
+// unary       = "NOT" unary | wire | literal
+struct Parser<'a, W> {
+    tokens: Vec<Token<'a, W>>,
+    position: usize,
+}
+
+impl<'a, W: Word> Parser<'a, W> {
+    fn next(&mut self) -> &Token<'a, W> {
+        let token = &self.tokens[self.position];
+        if self.position + 1 < self.tokens.len() {
+            self.position += 1;
+        }
+        token
+    }
+
+    fn peek(&self) -> &Token<'a, W> {
+        &self.tokens[self.position]
+    }
+
+    fn parse_unary(&mut self) -> Result<Expression<W>, ParseError> {
+        let token = self.next();
+        match token.kind {
+            TokenKind::Not => {
+                let inner = self.parse_unary()?;
+                Ok(Complement(Box::new(inner)))
+            }
+            TokenKind::Wire => Ok(Register(token.text.to_owned())),
+            TokenKind::Literal => {
+                W::from_str(token.text).map(Literal).map_err(|_| {
+                    token.error(ParseErrorKind::LiteralTooWide(token.text.to_owned(), W::BITS))
+                })
+            }
+            _ => {
+                Err(token.error(ParseErrorKind::Expected("a wire or a literal",
+                                                         token.describe())))
+            }
+        }
+    }
+
+    fn parse_expression(&mut self) -> Result<Expression<W>, ParseError> {
+        let left = self.parse_unary()?;
+        let operator = match self.peek().kind {
+            TokenKind::Binary(operator) => operator,
+            _ => return Ok(left),
+        };
+        self.next();
+        let right = self.parse_unary()?;
+
+        let token = self.peek();
+        if let TokenKind::Binary(_) = token.kind {
+            return Err(token.error(ParseErrorKind::ChainedOperator(token.text.to_owned())));
+        }
+        Ok(operator(Box::new(left), Box::new(right)))
+    }
+
+    fn expect(&mut self,
+              expected: &'static str,
+              matches: fn(&TokenKind<W>) -> bool)
+              -> Result<&Token<'a, W>, ParseError> {
+        let token = self.next();
+        if matches(&token.kind) {
+            Ok(token)
+        } else {
+            Err(token.error(ParseErrorKind::Expected(expected, token.describe())))
+        }
+    }
+}
+
+impl<W: Word> Instruction<W> {
+    pub fn try_parse(line: &str) -> Result<Self, ParseError> {
+        let mut parser = Parser {
+            tokens: tokenize(line)?,
+            position: 0,
+        };
+        let expression = parser.parse_expression()?;
+        parser.expect("an operator or '->'", |k| matches!(*k, TokenKind::Arrow))?;
+        let register = parser.expect("a target wire", |k| matches!(*k, TokenKind::Wire))?
+                             .text
+                             .to_owned();
+        parser.expect("the end of the line", |k| matches!(*k, TokenKind::End))?;
+        Ok(Instruction::new(&register, expression))
+    }
+}
+
+impl fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ParseErrorKind::UnexpectedCharacter(c) => write!(f, "Unexpected character '{}'", c),
+            ParseErrorKind::InvalidLiteral(ref literal) => {
+                write!(f, "'{}' is neither a literal nor a wire", literal)
+            }
+            ParseErrorKind::LiteralTooWide(ref literal, bits) => {
+                write!(f, "Literal {} doesn't fit into {} bits", literal, bits)
+            }
+            ParseErrorKind::UnknownOperator(ref operator) => {
+                write!(f, "Unknown operator '{}'", operator)
+            }
+            ParseErrorKind::ChainedOperator(ref operator) => {
+                write!(f, "Operators can't be chained, found another '{}'", operator)
+            }
+            ParseErrorKind::Expected(expected, ref found) => {
+                write!(f, "Expected {}, found {}", expected, found)
+            }
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at column {}", self.kind, self.column)
+    }
+}
+
+#[cfg(test)]
+fn parse_error(line: &str) -> String {
+    Instruction::<u16>::try_parse(line).unwrap_err().to_string()
+}
+
+#[test]
+fn test_parse_nested() {
+    let x = || Box::new(Register("x".to_owned()));
+    let y = || Box::new(Register("y".to_owned()));
+    assert_eq!(Instruction::parse("NOT x AND y -> a"),
+               Instruction::new("a", And(Box::new(Complement(x())), y())));
+    assert_eq!(Instruction::parse("x OR NOT NOT y -> a"),
+               Instruction::new("a", Or(x(), Box::new(Complement(Box::new(Complement(y())))))));
+    assert_eq!(Instruction::parse("  x   RSHIFT 3->a1 "),
+               Instruction::new("a1", RShift(x(), Box::new(Literal(3)))));
+}
+
+#[test]
+fn test_parse_errors() {
+    assert_eq!(parse_error("x AN y -> d"), "Unknown operator 'AN' at column 3");
+    assert_eq!(parse_error("x AND y OR z -> d"),
+               "Operators can't be chained, found another 'OR' at column 9");
+    assert_eq!(parse_error("x y -> d"), "Expected an operator or '->', found 'y' at column 3");
+    assert_eq!(parse_error("x AND -> d"), "Expected a wire or a literal, found '->' at column 7");
+    assert_eq!(parse_error("x -> 5"), "Expected a target wire, found '5' at column 6");
+    assert_eq!(parse_error("x ->"),
+               "Expected a target wire, found the end of the line at column 5");
+    assert_eq!(parse_error("x -> d e"), "Expected the end of the line, found 'e' at column 8");
+    assert_eq!(parse_error("12ab -> d"), "'12ab' is neither a literal nor a wire at column 1");
+    assert_eq!(parse_error("x & y -> d"), "Unexpected character '&' at column 3");
+    assert_eq!(parse_error("NOT 65536 -> d"),
+               "Literal 65536 doesn't fit into 16 bits at column 5");
+    assert_eq!(Instruction::<u16>::try_parse("x AND"),
+               Err(ParseError {
+                   column: 6,
+                   kind: ParseErrorKind::Expected("a wire or a literal",
+                                                  "the end of the line".to_owned()),
+               }));
+}
@@ -0,0 +1,337 @@
+use std::collections::HashMap;
+
+use {CPU, Expression, EvaluationError, Word};
+use EvaluationError::*;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Operand<W> {
+    Slot(usize),
+    Literal(W),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Gate<W> {
+    Copy(Operand<W>),
+    And(Operand<W>, Operand<W>),
+    Or(Operand<W>, Operand<W>),
+    LShift(Operand<W>, Operand<W>),
+    RShift(Operand<W>, Operand<W>),
+    Complement(Operand<W>),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Step<W> {
+    target: usize,
+    gate: Gate<W>,
+}
+
+enum Visit {
+    InProgress,
+    Done,
+}
+
+// A netlist flattened into steps that only ever read slots written by earlier
+// steps, so a single pass over them evaluates every wire.
+pub struct Program<W> {
+    slots: HashMap<String, usize>,
+    slot_count: usize,
+    steps: Vec<Step<W>>,
+}
+
+impl<W: Word> CPU<W> {
+    // Depth first search with an explicit stack, so that long chains of wires
+    // can't overflow the call stack. Undriven wires are either an error or
+    // left out of the order, to be treated as inputs by the caller.
+    pub fn sort_topologically<'a>(&'a self,
+                                  outputs: &[&'a str],
+                                  allow_undriven: bool)
+                                  -> Result<Vec<&'a str>, EvaluationError> {
+        let mut visits = HashMap::new();
+        let mut order = Vec::new();
+
+        for &output in outputs {
+            if visits.contains_key(output) {
+                continue;
+            }
+
+            let mut stack = Vec::new();
+            let chain = |stack: &Vec<(&str, Vec<&str>, usize)>, register: &str| {
+                let mut chain = stack.iter().map(|f| f.0.to_owned()).collect::<Vec<_>>();
+                chain.push(register.to_owned());
+                chain
+            };
+
+            match self.registers.get(output) {
+                Some(expression) => stack.push((output, expression.get_registers(), 0)),
+                None if allow_undriven => continue,
+                None => return Err(Undriven(vec![output.to_owned()])),
+            }
+            visits.insert(output, Visit::InProgress);
+
+            while !stack.is_empty() {
+                let next = {
+                    let frame = stack.last_mut().unwrap();
+                    frame.2 += 1;
+                    frame.1.get(frame.2 - 1).cloned()
+                };
+
+                match next {
+                    Some(dependency) => {
+                        match visits.get(dependency) {
+                            Some(&Visit::Done) => continue,
+                            Some(&Visit::InProgress) => {
+                                return Err(Cycle(chain(&stack, dependency)))
+                            }
+                            None => {}
+                        }
+                        match self.registers.get_key_value(dependency) {
+                            Some((register, expression)) => {
+                                visits.insert(register, Visit::InProgress);
+                                stack.push((register, expression.get_registers(), 0));
+                            }
+                            None if allow_undriven => {
+                                visits.insert(dependency, Visit::Done);
+                            }
+                            None => return Err(Undriven(chain(&stack, dependency))),
+                        }
+                    }
+                    None => {
+                        let (register, _, _) = stack.pop().unwrap();
+                        visits.insert(register, Visit::Done);
+                        order.push(register);
+                    }
+                }
+            }
+        }
+
+        Ok(order)
+    }
+}
+
+impl<W: Word> Program<W> {
+    pub fn compile(cpu: &CPU<W>, outputs: &[&str]) -> Result<Self, EvaluationError> {
+        let order = cpu.sort_topologically(outputs, false)?;
+
+        let mut program = Program {
+            slots: HashMap::new(),
+            slot_count: 0,
+            steps: Vec::new(),
+        };
+
+        for register in order {
+            let target = program.allocate_slot();
+            program.slots.insert(register.to_owned(), target);
+            program.compile_into(&cpu.registers[register], target);
+        }
+
+        Ok(program)
+    }
+
+    fn allocate_slot(&mut self) -> usize {
+        self.slot_count += 1;
+        self.slot_count - 1
+    }
+
+    fn compile_operand(&mut self, expression: &Expression<W>) -> Operand<W> {
+        match *expression {
+            Expression::Register(ref register) => Operand::Slot(self.slots[register]),
+            Expression::Literal(literal) => Operand::Literal(literal),
+            _ => {
+                let target = self.allocate_slot();
+                self.compile_into(expression, target);
+                Operand::Slot(target)
+            }
+        }
+    }
+
+    fn compile_into(&mut self, expression: &Expression<W>, target: usize) {
+        let gate = match *expression {
+            Expression::Register(_) |
+            Expression::Literal(_) => Gate::Copy(self.compile_operand(expression)),
+            Expression::And(ref left, ref right) => {
+                Gate::And(self.compile_operand(left), self.compile_operand(right))
+            }
+            Expression::Or(ref left, ref right) => {
+                Gate::Or(self.compile_operand(left), self.compile_operand(right))
+            }
+            Expression::LShift(ref left, ref right) => {
+                Gate::LShift(self.compile_operand(left), self.compile_operand(right))
+            }
+            Expression::RShift(ref left, ref right) => {
+                Gate::RShift(self.compile_operand(left), self.compile_operand(right))
+            }
+            Expression::Complement(ref expression) => {
+                Gate::Complement(self.compile_operand(expression))
+            }
+        };
+        self.steps.push(Step {
+            target,
+            gate,
+        });
+    }
+
+    pub fn get_slot(&self, register: &str) -> Option<usize> {
+        self.slots.get(register).cloned()
+    }
+
+    pub fn run(&self) -> Vec<W> {
+        let mut values = vec![W::default(); self.slot_count];
+
+        for step in &self.steps {
+            let value = {
+                let get = |operand| {
+                    match operand {
+                        Operand::Slot(slot) => values[slot],
+                        Operand::Literal(literal) => literal,
+                    }
+                };
+                match step.gate {
+                    Gate::Copy(operand) => get(operand),
+                    Gate::And(left, right) => get(left) & get(right),
+                    Gate::Or(left, right) => get(left) | get(right),
+                    Gate::LShift(left, right) => get(left).shift_left(get(right)),
+                    Gate::RShift(left, right) => get(left).shift_right(get(right)),
+                    Gate::Complement(operand) => !get(operand),
+                }
+            };
+            values[step.target] = value;
+        }
+
+        values
+    }
+
+    pub fn evaluate(&self, register: &str) -> Option<W> {
+        self.get_slot(register).map(|slot| self.run()[slot])
+    }
+
+    // Evaluates 64 different assignments at once. Every slot holds one word
+    // per bit of the wire, whose n-th bit belongs to the n-th assignment. The
+    // given input slots are set to their bit planes instead of being computed.
+    pub fn run_sliced(&self, inputs: &HashMap<usize, Vec<u64>>) -> Vec<Vec<u64>> {
+        let bits = W::BITS as usize;
+        let mut values = vec![vec![0; bits]; self.slot_count];
+
+        for step in &self.steps {
+            if let Some(planes) = inputs.get(&step.target) {
+                values[step.target] = planes.clone();
+                continue;
+            }
+
+            let planes = {
+                let get = |operand: Operand<W>| {
+                    match operand {
+                        Operand::Slot(slot) => values[slot].clone(),
+                        Operand::Literal(literal) => {
+                            let literal = literal.to_u64();
+                            (0..bits).map(|i| if literal >> i & 1 == 1 { !0 } else { 0 }).collect()
+                        }
+                    }
+                };
+                match step.gate {
+                    Gate::Copy(operand) => get(operand),
+                    Gate::And(left, right) => {
+                        get(left).iter().zip(get(right)).map(|(l, r)| l & r).collect()
+                    }
+                    Gate::Or(left, right) => {
+                        get(left).iter().zip(get(right)).map(|(l, r)| l | r).collect()
+                    }
+                    Gate::LShift(left, right) => shift_sliced(get(left), &get(right), true),
+                    Gate::RShift(left, right) => shift_sliced(get(left), &get(right), false),
+                    Gate::Complement(operand) => get(operand).iter().map(|p| !p).collect(),
+                }
+            };
+            values[step.target] = planes;
+        }
+
+        values
+    }
+}
+
+// A barrel shifter: every bit of the amount conditionally shifts by the
+// matching power of two, so amounts of the width or more shift out everything.
+fn shift_sliced(mut value: Vec<u64>, amount: &[u64], left: bool) -> Vec<u64> {
+    let bits = value.len();
+    for (j, &select) in amount.iter().enumerate() {
+        if select == 0 {
+            continue;
+        }
+        let distance = 1usize.checked_shl(j as u32).unwrap_or(bits);
+        let shifted = (0..bits)
+                          .map(|i| {
+                              let source = if left {
+                                  i.checked_sub(distance)
+                              } else {
+                                  i.checked_add(distance).filter(|&s| s < bits)
+                              };
+                              source.map_or(0, |s| value[s])
+                          })
+                          .collect::<Vec<_>>();
+        for (bit, shifted) in value.iter_mut().zip(shifted) {
+            *bit = (select & shifted) | (!select & *bit);
+        }
+    }
+    value
+}
+
+#[cfg(test)]
+use Instruction;
+
+#[test]
+fn test_compiled_execute() {
+    let mut cpu = CPU::new();
+    cpu.insert(Instruction::parse("NOT y -> i"));
+    cpu.insert(Instruction::parse("x OR y -> e"));
+    cpu.insert(Instruction::parse("123 -> x"));
+    cpu.insert(Instruction::parse("456 -> y"));
+    cpu.insert(Instruction::parse("x AND y -> d"));
+    cpu.insert(Instruction::parse("x LSHIFT 2 -> f"));
+    cpu.insert(Instruction::parse("y RSHIFT 2 -> g"));
+    cpu.insert(Instruction::parse("NOT x -> h"));
+
+    let wires = ["d", "e", "f", "g", "h", "i", "x", "y"];
+    let program = Program::compile(&cpu, &wires).unwrap();
+    let values = program.run();
+    for wire in &wires {
+        assert_eq!(Some(values[program.get_slot(wire).unwrap()]), cpu.evaluate(wire));
+    }
+    assert_eq!(program.evaluate("d"), Some(72));
+    assert_eq!(program.evaluate("h"), Some(65412));
+}
+
+#[test]
+fn test_compiled_nested_expressions() {
+    let mut cpu = CPU::new();
+    cpu.insert(Instruction::parse("NOT x AND y -> a"));
+    cpu.insert(Instruction::parse("12 -> x"));
+    cpu.insert(Instruction::parse("10 -> y"));
+    let program = Program::compile(&cpu, &["a"]).unwrap();
+    assert_eq!(program.evaluate("a"), cpu.evaluate("a"));
+    assert_eq!(program.evaluate("a"), Some(2));
+}
+
+#[test]
+fn test_compiled_loop_handling() {
+    let mut cpu = CPU::new();
+    cpu.insert(Instruction::parse("5 AND x -> a"));
+    cpu.insert(Instruction::parse("NOT x -> b"));
+    cpu.insert(Instruction::parse("y AND b -> x"));
+    cpu.insert(Instruction::parse("456 -> y"));
+    assert_eq!(Program::compile(&cpu, &["a"]).err(), cpu.try_evaluate("a").err());
+
+    cpu.insert(Instruction::parse("z -> y"));
+    cpu.insert(Instruction::parse("7 -> x"));
+    assert_eq!(Program::compile(&cpu, &["a", "b"]).err(), cpu.try_evaluate("b").err());
+}
+
+#[test]
+fn test_compiled_deep_chain() {
+    let mut cpu = CPU::new();
+    cpu.insert(Instruction::parse("1 -> w0"));
+    for i in 1..200_000 {
+        let previous = Expression::Register(format!("w{}", i - 1));
+        cpu.insert(Instruction::new(&format!("w{}", i),
+                                    Expression::Complement(Box::new(previous))));
+    }
+    let program = Program::compile(&cpu, &["w199999"]).unwrap();
+    assert_eq!(program.evaluate("w199999"), Some(0xFFFE));
+}
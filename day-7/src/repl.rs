@@ -0,0 +1,164 @@
+use std::io::{self, BufRead, Write};
+
+use {CPU, Instruction, Word, parse_instructions};
+
+const COMMANDS: &str = "eval, set, unset, deps, users, add, reset or quit";
+
+// Keeps the circuit around between commands, so cached values only get
+// recomputed once something they depend on changes.
+pub struct Repl<'a, W> {
+    netlist: &'a [String],
+    cpu: CPU<W>,
+}
+
+impl<'a, W: Word> Repl<'a, W> {
+    pub fn new(netlist: &'a [String], cpu: CPU<W>) -> Self {
+        Repl { netlist, cpu }
+    }
+
+    pub fn run<R, O>(&mut self, input: R, mut output: O) -> io::Result<()>
+        where R: BufRead,
+              O: Write
+    {
+        let mut lines = input.lines();
+        loop {
+            write!(output, "> ")?;
+            output.flush()?;
+            let line = match lines.next() {
+                Some(line) => line?,
+                None => return writeln!(output),
+            };
+            let line = line.trim();
+            if line == "quit" || line == "exit" {
+                return Ok(());
+            }
+            if !line.is_empty() {
+                writeln!(output, "{}", self.execute(line))?;
+            }
+        }
+    }
+
+    pub fn execute(&mut self, line: &str) -> String {
+        let (command, rest) = match line.find(' ') {
+            Some(index) => (&line[..index], line[index + 1..].trim()),
+            None => (line, ""),
+        };
+        let words = rest.split_whitespace().collect::<Vec<_>>();
+
+        match (command, words.as_slice()) {
+            ("eval", &[register]) => {
+                match self.cpu.try_evaluate(register) {
+                    Ok(value) => format!("{} = {}", register, value),
+                    Err(err) => format!("Can't evaluate {}: {}", register, err),
+                }
+            }
+            ("set", &[register, value]) => {
+                match W::from_str(value) {
+                    Ok(value) => {
+                        self.cpu.override_wire(register, value);
+                        format!("{} = {} (override)", register, value)
+                    }
+                    Err(_) => format!("{} isn't a value that fits into {} bits", value, W::BITS),
+                }
+            }
+            ("unset", &[register]) => {
+                if self.cpu.overrides.contains_key(register) {
+                    self.cpu.remove_override(register);
+                    format!("Removed the override of {}", register)
+                } else {
+                    format!("{} isn't overridden", register)
+                }
+            }
+            ("deps", &[register]) => {
+                match self.cpu.registers.get(register) {
+                    Some(expression) => format!("{} -> {}", expression, register),
+                    None => format!("{} is never driven", register),
+                }
+            }
+            ("users", &[register]) => {
+                let mut users = self.cpu
+                                    .users
+                                    .get(register)
+                                    .map(|users| users.iter().cloned().collect::<Vec<_>>())
+                                    .unwrap_or_default();
+                users.sort();
+                if users.is_empty() {
+                    format!("{} isn't used by any wire", register)
+                } else {
+                    users.join(", ")
+                }
+            }
+            ("add", _) if !rest.is_empty() => {
+                match Instruction::try_parse(rest) {
+                    Ok(instruction) => {
+                        let description = instruction.to_string();
+                        self.cpu.insert(instruction);
+                        format!("Added {}", description)
+                    }
+                    Err(err) => format!("Can't add '{}': {}", rest, err),
+                }
+            }
+            ("reset", &[]) => {
+                let mut cpu = CPU::new();
+                cpu.insert_all(parse_instructions(self.netlist).unwrap());
+                self.cpu = cpu;
+                "Reset the circuit to the loaded netlist".to_owned()
+            }
+            ("eval", _) | ("unset", _) | ("deps", _) | ("users", _) => {
+                format!("Usage: {} <wire>", command)
+            }
+            ("set", _) => "Usage: set <wire> <value>".to_owned(),
+            ("add", _) => "Usage: add <instruction>".to_owned(),
+            ("reset", _) => "Usage: reset".to_owned(),
+            _ => format!("Unknown command '{}', expected {}", command, COMMANDS),
+        }
+    }
+}
+
+#[test]
+fn test_repl() {
+    let netlist = ["123 -> x", "456 -> y", "x AND y -> d", "d OR 1 -> e"]
+                      .iter()
+                      .map(|l| l.to_string())
+                      .collect::<Vec<_>>();
+    let mut cpu = CPU::<u16>::new();
+    cpu.insert_all(parse_instructions(&netlist).unwrap());
+    let mut repl = Repl::new(&netlist, cpu);
+
+    assert_eq!(repl.execute("eval e"), "e = 73");
+    assert!(repl.cpu.cache.contains_key("d"));
+    assert_eq!(repl.execute("set x 65535"), "x = 65535 (override)");
+    assert_eq!(repl.execute("eval e"), "e = 457");
+    assert_eq!(repl.execute("set x 65536"), "65536 isn't a value that fits into 16 bits");
+    assert_eq!(repl.execute("unset x"), "Removed the override of x");
+    assert_eq!(repl.execute("unset x"), "x isn't overridden");
+    assert_eq!(repl.execute("eval e"), "e = 73");
+    assert_eq!(repl.execute("deps d"), "x AND y -> d");
+    assert_eq!(repl.execute("deps z"), "z is never driven");
+    assert_eq!(repl.execute("users y"), "d");
+    assert_eq!(repl.execute("add z AND y -> x"), "Added z AND y -> x");
+    assert_eq!(repl.execute("users y"), "d, x");
+    assert_eq!(repl.execute("eval e"),
+               "Can't evaluate e: Wire 'z' is never driven, needed by e -> d -> x -> z");
+    assert_eq!(repl.execute("add 70000 -> z"),
+               "Can't add '70000 -> z': Literal 70000 doesn't fit into 16 bits at column 1");
+    assert_eq!(repl.execute("reset"), "Reset the circuit to the loaded netlist");
+    assert_eq!(repl.execute("eval e"), "e = 73");
+    assert_eq!(repl.execute("eval"), "Usage: eval <wire>");
+    assert_eq!(repl.execute("frobnicate x"),
+               "Unknown command 'frobnicate', expected eval, set, unset, deps, users, add, \
+                reset or quit");
+}
+
+#[test]
+fn test_repl_session() {
+    let netlist = vec!["123 -> x".to_owned(), "NOT x -> h".to_owned()];
+    let mut cpu = CPU::<u16>::new();
+    cpu.insert_all(parse_instructions(&netlist).unwrap());
+
+    let input = "eval h\n\nset x 0\neval h\nquit\neval x\n";
+    let mut output = Vec::new();
+    Repl::new(&netlist, cpu).run(input.as_bytes(), &mut output).unwrap();
+    assert_eq!(String::from_utf8(output).unwrap(),
+               "> h = 65412\n> > x = 0 (override)\n> h = 65535\n> ");
+}
@@ -0,0 +1,173 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use {CPU, EvaluationError, Expression, Word};
+use program::Program;
+
+// Searching more than this many free bits exhaustively takes too long.
+const MAX_FREE_BITS: u32 = 24;
+
+// The bit planes of the lowest six bits of the assignment index, one lane
+// per assignment in a batch of 64.
+const LANE_PATTERNS: [u64; 6] = [0xAAAA_AAAA_AAAA_AAAA,
+                                 0xCCCC_CCCC_CCCC_CCCC,
+                                 0xF0F0_F0F0_F0F0_F0F0,
+                                 0xFF00_FF00_FF00_FF00,
+                                 0xFFFF_0000_FFFF_0000,
+                                 0xFFFF_FFFF_0000_0000];
+
+#[derive(Debug, PartialEq)]
+pub enum SolveError {
+    Evaluation(EvaluationError),
+    TooManyFreeBits(u32),
+}
+
+impl fmt::Display for SolveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SolveError::Evaluation(ref err) => write!(f, "{}", err),
+            SolveError::TooManyFreeBits(bits) => {
+                write!(f,
+                       "{} free bits are too many to search, at most {} are supported",
+                       bits,
+                       MAX_FREE_BITS)
+            }
+        }
+    }
+}
+
+impl<W: Word> CPU<W> {
+    // Finds every assignment of the free wires that makes the output carry the
+    // target value. The free wires' drivers are ignored and the rest of the
+    // netlist is evaluated for all assignments, 64 at a time, so an empty
+    // result means that no assignment exists.
+    pub fn solve(&self,
+                 free: &[&str],
+                 output: &str,
+                 target: W)
+                 -> Result<Vec<Vec<W>>, SolveError> {
+        let width = W::BITS;
+        let free_bits = width * free.len() as u32;
+        if free_bits > MAX_FREE_BITS {
+            return Err(SolveError::TooManyFreeBits(free_bits));
+        }
+
+        let mut constraints = CPU::new();
+        constraints.registers = self.registers.clone();
+        for &register in free {
+            constraints.registers.insert(register.to_owned(), Expression::Literal(W::default()));
+        }
+        let program = Program::compile(&constraints, &[output]).map_err(SolveError::Evaluation)?;
+        let output = program.get_slot(output).unwrap();
+
+        let mut solutions = Vec::new();
+        // With fewer than 64 assignments, only the first lanes of the single
+        // batch hold real ones. As every wire has at least 8 bits, that only
+        // happens without any free wires, where the one assignment is empty.
+        let batches = (1u64 << free_bits).div_ceil(64);
+        let lanes = if free_bits < 6 {
+            (1u64 << (1 << free_bits)) - 1
+        } else {
+            !0
+        };
+        for batch in 0..batches {
+            let plane = |bit: u32| {
+                if bit < 6 {
+                    LANE_PATTERNS[bit as usize]
+                } else if batch >> (bit - 6) & 1 == 1 {
+                    !0
+                } else {
+                    0
+                }
+            };
+
+            let mut inputs = HashMap::new();
+            for (k, register) in free.iter().enumerate() {
+                if let Some(slot) = program.get_slot(register) {
+                    let planes = (0..width).map(|i| plane(k as u32 * width + i)).collect();
+                    inputs.insert(slot, planes);
+                }
+            }
+
+            let values = program.run_sliced(&inputs);
+            let mut matches = lanes;
+            for (i, &bit) in values[output].iter().enumerate() {
+                matches &= if target.to_u64() >> i & 1 == 1 { bit } else { !bit };
+            }
+
+            while matches != 0 {
+                let lane = matches.trailing_zeros() as u64;
+                matches &= matches - 1;
+                let assignment = batch * 64 + lane;
+                solutions.push((0..free.len() as u32)
+                                   .map(|k| W::truncate(assignment >> (k * width)))
+                                   .collect());
+            }
+        }
+
+        Ok(solutions)
+    }
+}
+
+#[cfg(test)]
+use Instruction;
+
+#[test]
+fn test_solve() {
+    let mut cpu = CPU::new();
+    cpu.insert(Instruction::parse("123 -> x"));
+    cpu.insert(Instruction::parse("x LSHIFT 2 -> f"));
+    assert_eq!(cpu.solve(&["x"], "f", 492),
+               Ok(vec![vec![123], vec![16507], vec![32891], vec![49275]]));
+    assert_eq!(cpu.solve(&["x"], "f", 1), Ok(vec![]));
+    assert_eq!(cpu.solve(&["x", "f"], "f", 1), Err(SolveError::TooManyFreeBits(32)));
+
+    cpu.insert(Instruction::parse("f OR z -> g"));
+    assert_eq!(cpu.solve(&["x"], "g", 0).unwrap_err().to_string(),
+               "Wire 'z' is never driven, needed by g -> z");
+}
+
+#[test]
+fn test_solve_without_free_wires() {
+    let mut cpu = CPU::<u8>::new();
+    cpu.insert(Instruction::try_parse("x AND 5 -> a").unwrap());
+    cpu.insert(Instruction::try_parse("3 -> x").unwrap());
+    cpu.insert(Instruction::try_parse("a OR 250 -> b").unwrap());
+    assert_eq!(cpu.solve(&[], "a", 1), Ok(vec![vec![]]));
+    assert_eq!(cpu.solve(&[], "a", 3), Ok(vec![]));
+    assert_eq!(cpu.solve(&[], "b", 251), Ok(vec![vec![]]));
+    assert_eq!(cpu.solve(&[], "b", 255), Ok(vec![]));
+}
+
+#[test]
+fn test_solve_several_wires() {
+    let mut cpu = CPU::<u8>::new();
+    cpu.insert(Instruction::try_parse("x AND y -> a").unwrap());
+    cpu.insert(Instruction::try_parse("NOT y -> b").unwrap());
+    cpu.insert(Instruction::try_parse("1 LSHIFT y -> c").unwrap());
+    cpu.insert(Instruction::try_parse("128 RSHIFT y -> d").unwrap());
+    assert_eq!(cpu.solve(&["x", "y"], "a", 255), Ok(vec![vec![255, 255]]));
+    assert_eq!(cpu.solve(&["y"], "b", 15), Ok(vec![vec![240]]));
+    assert_eq!(cpu.solve(&["y"], "c", 4), Ok(vec![vec![2]]));
+    assert_eq!(cpu.solve(&["y"], "c", 0).unwrap(),
+               (8..=255).map(|y| vec![y]).collect::<Vec<_>>());
+    assert_eq!(cpu.solve(&["y"], "d", 1), Ok(vec![vec![7]]));
+}
+
+#[test]
+fn test_solve_input() {
+    use std::path::Path;
+    use read_file;
+
+    let mut cpu = CPU::new();
+    for line in read_file(Path::new("input.txt")) {
+        cpu.insert(Instruction::parse(&line));
+    }
+
+    let solutions = cpu.solve(&["b"], "a", 3176).unwrap();
+    assert!(!solutions.is_empty());
+    for solution in solutions {
+        cpu.override_wire("b", solution[0]);
+        assert_eq!(cpu.evaluate("a"), Some(3176));
+    }
+}
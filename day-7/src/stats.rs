@@ -0,0 +1,162 @@
+use std::cmp;
+use std::collections::HashMap;
+use std::fmt;
+
+use {CPU, Expression, EvaluationError, Word};
+use Expression::*;
+
+const OPERATORS: [&str; 5] = ["AND", "OR", "LSHIFT", "RSHIFT", "NOT"];
+
+struct WireStats {
+    name: String,
+    depth: usize,
+    fan_out: usize,
+}
+
+pub struct Stats {
+    gates: [usize; 5],
+    inputs: Vec<String>,
+    wires: Vec<WireStats>,
+}
+
+fn count_gates<W>(expression: &Expression<W>, gates: &mut [usize; 5]) {
+    let (operator, operands) = match *expression {
+        Register(_) | Literal(_) => return,
+        And(ref left, ref right) => (0, vec![left, right]),
+        Or(ref left, ref right) => (1, vec![left, right]),
+        LShift(ref left, ref right) => (2, vec![left, right]),
+        RShift(ref left, ref right) => (3, vec![left, right]),
+        Complement(ref expression) => (4, vec![expression]),
+    };
+    gates[operator] += 1;
+    for operand in operands {
+        count_gates(operand, gates);
+    }
+}
+
+// The number of gates on the longest path from an input or a literal.
+fn depth<W>(expression: &Expression<W>, depths: &HashMap<&str, usize>) -> usize {
+    match *expression {
+        Register(ref register) => depths.get(register.as_str()).cloned().unwrap_or(0),
+        Literal(_) => 0,
+        And(ref left, ref right) |
+        Or(ref left, ref right) |
+        LShift(ref left, ref right) |
+        RShift(ref left, ref right) => 1 + cmp::max(depth(left, depths), depth(right, depths)),
+        Complement(ref expression) => 1 + depth(expression, depths),
+    }
+}
+
+impl<W: Word> CPU<W> {
+    pub fn stats(&self) -> Result<Stats, EvaluationError> {
+        let mut registers = self.registers.keys().map(|r| r.as_str()).collect::<Vec<_>>();
+        registers.sort();
+        let order = self.sort_topologically(&registers, true)?;
+
+        let mut gates = [0; 5];
+        let mut depths = HashMap::new();
+        let mut fan_outs = HashMap::new();
+        for register in order {
+            let expression = &self.registers[register];
+            count_gates(expression, &mut gates);
+            for dependency in expression.get_registers() {
+                *fan_outs.entry(dependency).or_insert(0) += 1;
+            }
+            depths.insert(register, depth(expression, &depths));
+        }
+
+        let mut inputs = fan_outs.keys()
+                                 .filter(|r| !self.registers.contains_key(**r))
+                                 .map(|r| r.to_string())
+                                 .collect::<Vec<_>>();
+        inputs.sort();
+
+        let mut wires = registers.into_iter()
+                                 .chain(inputs.iter().map(|r| r.as_str()))
+                                 .map(|r| {
+                                     WireStats {
+                                         name: r.to_owned(),
+                                         depth: depths.get(r).cloned().unwrap_or(0),
+                                         fan_out: fan_outs.get(r).cloned().unwrap_or(0),
+                                     }
+                                 })
+                                 .collect::<Vec<_>>();
+        wires.sort_by(|a, b| b.depth.cmp(&a.depth).then_with(|| a.name.cmp(&b.name)));
+
+        Ok(Stats {
+            gates,
+            inputs,
+            wires,
+        })
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let gates = OPERATORS.iter()
+                             .zip(&self.gates)
+                             .map(|(operator, count)| format!("{} {}", operator, count))
+                             .collect::<Vec<_>>();
+        writeln!(f,
+                 "Gates: {} (total {})",
+                 gates.join(", "),
+                 self.gates.iter().sum::<usize>())?;
+        let inputs = if self.inputs.is_empty() {
+            "none".to_owned()
+        } else {
+            self.inputs.join(", ")
+        };
+        writeln!(f, "Wires: {}, inputs: {}", self.wires.len(), inputs)?;
+        if let Some(deepest) = self.wires.first() {
+            writeln!(f, "Logic depth: {} (wire {})", deepest.depth, deepest.name)?;
+        }
+        let widest = self.wires.iter().max_by_key(|w| (w.fan_out, cmp::Reverse(&w.name)));
+        if let Some(widest) = widest {
+            writeln!(f, "Largest fan-out: {} (wire {})", widest.fan_out, widest.name)?;
+        }
+        writeln!(f, "{:<8} {:>5} {:>7}", "wire", "depth", "fan-out")?;
+        for wire in &self.wires {
+            writeln!(f, "{:<8} {:>5} {:>7}", wire.name, wire.depth, wire.fan_out)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+use Instruction;
+
+#[test]
+fn test_stats() {
+    let mut cpu = CPU::new();
+    cpu.insert(Instruction::parse("123 -> x"));
+    cpu.insert(Instruction::parse("x AND y -> d"));
+    cpu.insert(Instruction::parse("NOT x OR d -> e"));
+    cpu.insert(Instruction::parse("e LSHIFT 2 -> f"));
+    cpu.insert(Instruction::parse("x RSHIFT x -> g"));
+
+    let stats = cpu.stats().unwrap();
+    assert_eq!(stats.gates, [1, 1, 1, 1, 1]);
+    assert_eq!(stats.inputs, vec!["y".to_owned()]);
+    let wires = stats.wires
+                     .iter()
+                     .map(|w| (w.name.as_str(), w.depth, w.fan_out))
+                     .collect::<Vec<_>>();
+    assert_eq!(wires,
+               vec![("f", 3, 0), ("e", 2, 1), ("d", 1, 1), ("g", 1, 0), ("x", 0, 4),
+                    ("y", 0, 1)]);
+
+    let report = stats.to_string();
+    assert!(report.starts_with("Gates: AND 1, OR 1, LSHIFT 1, RSHIFT 1, NOT 1 (total 5)\n\
+                                Wires: 6, inputs: y\n\
+                                Logic depth: 3 (wire f)\n\
+                                Largest fan-out: 4 (wire x)\n"));
+    assert!(report.contains("\nf            3       0\n"));
+}
+
+#[test]
+fn test_stats_of_cycle() {
+    let mut cpu = CPU::new();
+    cpu.insert(Instruction::parse("NOT b -> a"));
+    cpu.insert(Instruction::parse("a AND 1 -> b"));
+    assert!(cpu.stats().is_err());
+}
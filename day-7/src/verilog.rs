@@ -0,0 +1,133 @@
+use std::fmt::Write;
+
+use {CPU, Expression, EvaluationError, Word};
+use Expression::*;
+use EvaluationError::*;
+
+// Wire names that would otherwise be read as Verilog-2005 keywords.
+const KEYWORDS: &[&str] = &["always", "and", "assign", "automatic", "begin", "buf", "bufif0",
+                            "bufif1", "case", "casex", "casez", "cell", "cmos", "config",
+                            "deassign", "default", "defparam", "design", "disable", "edge",
+                            "else", "end", "endcase", "endconfig", "endfunction", "endgenerate",
+                            "endmodule", "endprimitive", "endspecify", "endtable", "endtask",
+                            "event", "for", "force", "forever", "fork", "function", "generate",
+                            "genvar", "highz0", "highz1", "if", "ifnone", "incdir", "include",
+                            "initial", "inout", "input", "instance", "integer", "join", "large",
+                            "liblist", "library", "localparam", "macromodule", "medium",
+                            "module", "nand", "negedge", "nmos", "nor", "noshowcancelled", "not",
+                            "notif0", "notif1", "or", "output", "parameter", "pmos", "posedge",
+                            "primitive", "pull0", "pull1", "pulldown", "pullup",
+                            "pulsestyle_ondetect", "pulsestyle_onevent", "rcmos", "real",
+                            "realtime", "reg", "release", "repeat", "rnmos", "rpmos", "rtran",
+                            "rtranif0", "rtranif1", "scalared", "showcancelled", "signed",
+                            "small", "specify", "specparam", "strong0", "strong1", "supply0",
+                            "supply1", "table", "task", "time", "tran", "tranif0", "tranif1",
+                            "tri", "tri0", "tri1", "triand", "trior", "trireg", "unsigned",
+                            "use", "uwire", "vectored", "wait", "wand", "weak0", "weak1",
+                            "while", "wire", "wor", "xnor", "xor"];
+
+fn identifier(register: &str) -> String {
+    if KEYWORDS.contains(&register) {
+        format!("\\{} ", register)
+    } else {
+        register.to_owned()
+    }
+}
+
+impl<W: Word> CPU<W> {
+    fn to_verilog_expression(&self, expression: &Expression<W>) -> String {
+        let operand = |expression: &Expression<W>| {
+            match *expression {
+                Register(_) | Literal(_) => self.to_verilog_expression(expression),
+                _ => format!("({})", self.to_verilog_expression(expression)),
+            }
+        };
+        match *expression {
+            Register(ref register) => identifier(register),
+            Literal(literal) => format!("{}'d{}", W::BITS, literal),
+            And(ref left, ref right) => format!("{} & {}", operand(left), operand(right)),
+            Or(ref left, ref right) => format!("{} | {}", operand(left), operand(right)),
+            LShift(ref left, ref right) => format!("{} << {}", operand(left), operand(right)),
+            RShift(ref left, ref right) => format!("{} >> {}", operand(left), operand(right)),
+            Complement(ref expression) => format!("~{}", operand(expression)),
+        }
+    }
+
+    // A combinational module computing the given outputs. Undriven wires in
+    // their cones become inputs. Verilog's shifts also produce 0 once the
+    // amount reaches the width, so the semantics carry over unchanged.
+    pub fn to_verilog(&self, outputs: &[&str]) -> Result<String, EvaluationError> {
+        if let Some(output) = outputs.iter().find(|o| !self.registers.contains_key(**o)) {
+            return Err(Undriven(vec![output.to_string()]));
+        }
+        let order = self.sort_topologically(outputs, true)?;
+
+        let mut inputs = order.iter()
+                              .flat_map(|r| self.registers[*r].get_registers())
+                              .filter(|r| !self.registers.contains_key(*r))
+                              .collect::<Vec<_>>();
+        inputs.sort();
+        inputs.dedup();
+
+        let range = format!("[{}:0]", W::BITS - 1);
+        let ports = inputs.iter()
+                          .map(|r| format!("    input wire {} {}", range, identifier(r)))
+                          .chain(outputs.iter().map(|r| {
+                              format!("    output wire {} {}", range, identifier(r))
+                          }))
+                          .collect::<Vec<_>>();
+
+        let mut output = format!("module circuit(\n{}\n);\n", ports.join(",\n"));
+        for register in order.iter().filter(|r| !outputs.contains(r)) {
+            writeln!(output, "    wire {} {};", range, identifier(register)).unwrap();
+        }
+        for register in &order {
+            writeln!(output,
+                     "    assign {} = {};",
+                     identifier(register),
+                     self.to_verilog_expression(&self.registers[*register]))
+                .unwrap();
+        }
+        output.push_str("endmodule\n");
+
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+use Instruction;
+
+#[test]
+fn test_verilog_export() {
+    let mut cpu = CPU::new();
+    cpu.insert(Instruction::parse("x AND y -> d"));
+    cpu.insert(Instruction::parse("123 -> x"));
+    cpu.insert(Instruction::parse("d LSHIFT 2 -> if"));
+    cpu.insert(Instruction::parse("NOT x OR z -> e"));
+    cpu.insert(Instruction::parse("NOT w -> h"));
+
+    assert_eq!(cpu.to_verilog(&["if", "e"]).unwrap(),
+               "module circuit(\n    \
+                    input wire [15:0] y,\n    \
+                    input wire [15:0] z,\n    \
+                    output wire [15:0] \\if ,\n    \
+                    output wire [15:0] e\n\
+                );\n    \
+                wire [15:0] x;\n    \
+                wire [15:0] d;\n    \
+                assign x = 16'd123;\n    \
+                assign d = x & y;\n    \
+                assign \\if  = d << 16'd2;\n    \
+                assign e = (~x) | z;\n\
+                endmodule\n");
+}
+
+#[test]
+fn test_verilog_export_errors() {
+    let mut cpu = CPU::new();
+    cpu.insert(Instruction::parse("NOT b -> a"));
+    cpu.insert(Instruction::parse("a AND 1 -> b"));
+    assert_eq!(cpu.to_verilog(&["a"]),
+               Err(Cycle(vec!["a".to_owned(), "b".to_owned(), "a".to_owned()])));
+    assert_eq!(cpu.to_verilog(&["c"]), Err(Undriven(vec!["c".to_owned()])));
+}
@@ -1,9 +1,15 @@
 use std::path::Path;
 use std::io::prelude::*;
+use std::io::{self, BufReader};
 use std::fs::File;
 use std::char;
+use std::cmp;
+use std::fmt::{self, Write as FmtWrite};
+use std::env::args;
 
 use ParseState::*;
+use Expected::*;
+use ScanError::*;
 
 enum ParseState {
     NoString,
@@ -11,10 +17,157 @@ enum ParseState {
     Escaped,
     EscapedHexadecimal,
     EscapedHexadecimal1(char),
+    // The value and number of digits of an octal escape so far.
+    EscapedOctal(u32, usize),
+    EscapedUnicode,
+    EscapedUnicodeBraced(u32, usize),
+    // The high surrogate decoded so far, if this escape is the low half of a pair.
+    EscapedUnicodeFixed(Option<u32>, u32, usize),
+    // A \uD800 to \uDBFF escape that has to be followed by its low surrogate.
+    HighSurrogate(u32),
+    HighSurrogateEscaped(u32),
+    Finished,
 }
 
-type IntermediateScanResult = Result<Option<char>, &'static str>;
-type ParseResult = Result<String, &'static str>;
+#[derive(Debug, PartialEq)]
+enum Expected {
+    Char(char),
+    HexDigit,
+    OctalDigit,
+    End,
+}
+
+const QUOTE: &[Expected] = &[Char('"')];
+const ESCAPE: &[Expected] = &[Char('"'), Char('\\'), Char('x')];
+const HEX_DIGIT: &[Expected] = &[HexDigit];
+const HEX_DIGIT_OR_BRACE: &[Expected] = &[HexDigit, Char('}')];
+const OPEN_BRACE: &[Expected] = &[Char('{')];
+const CLOSE_BRACE: &[Expected] = &[Char('}')];
+const BACKSLASH: &[Expected] = &[Char('\\')];
+const UNICODE: &[Expected] = &[Char('u')];
+const END: &[Expected] = &[End];
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Unicode {
+    Unsupported,
+    // \u{1F600} with one to six digits, like Rust.
+    Braced,
+    // \u00e9 with exactly four digits, like JSON.
+    FourDigits,
+}
+
+// Decides which escape sequences a literal may contain. The encoder uses the
+// same tables, so everything it produces decodes again.
+struct Dialect {
+    name: &'static str,
+    // Escapes of a single character after the backslash and the character
+    // they stand for.
+    escapes: &'static [(char, char)],
+    // The largest value of a \xHH escape, if the dialect has them.
+    max_hex: Option<u32>,
+    // Whether one to three octal digits may follow a backslash, up to the
+    // same largest value.
+    octal: bool,
+    unicode: Unicode,
+    // Everything that may follow a backslash, for error messages.
+    expected: &'static [Expected],
+}
+
+const AOC: Dialect = Dialect {
+    name: "aoc",
+    escapes: &[('"', '"'), ('\\', '\\')],
+    max_hex: Some(0xFF),
+    octal: false,
+    unicode: Unicode::Unsupported,
+    expected: ESCAPE,
+};
+
+const RUST: Dialect = Dialect {
+    name: "rust",
+    escapes: &[('"', '"'), ('\\', '\\'), ('\'', '\''), ('n', '\n'), ('r', '\r'), ('t', '\t'),
+               ('0', '\0')],
+    max_hex: Some(0x7F),
+    octal: false,
+    unicode: Unicode::Braced,
+    expected: &[Char('"'), Char('\\'), Char('\''), Char('n'), Char('r'), Char('t'), Char('0'),
+                Char('x'), Char('u')],
+};
+
+const JSON: Dialect = Dialect {
+    name: "json",
+    escapes: &[('"', '"'), ('\\', '\\'), ('/', '/'), ('b', '\u{8}'), ('f', '\u{c}'),
+               ('n', '\n'), ('r', '\r'), ('t', '\t')],
+    max_hex: None,
+    octal: false,
+    unicode: Unicode::FourDigits,
+    expected: &[Char('"'), Char('\\'), Char('/'), Char('b'), Char('f'), Char('n'), Char('r'),
+                Char('t'), Char('u')],
+};
+
+// Hexadecimal escapes always take exactly two digits here, while C keeps
+// reading digits for as long as there are any. \0 is just the shortest octal
+// escape.
+const C: Dialect = Dialect {
+    name: "c",
+    escapes: &[('"', '"'), ('\\', '\\'), ('\'', '\''), ('?', '?'), ('a', '\u{7}'),
+               ('b', '\u{8}'), ('f', '\u{c}'), ('n', '\n'), ('r', '\r'), ('t', '\t'),
+               ('v', '\u{b}')],
+    max_hex: Some(0xFF),
+    octal: true,
+    unicode: Unicode::Unsupported,
+    expected: &[Char('"'), Char('\\'), Char('\''), Char('?'), Char('a'), Char('b'), Char('f'),
+                Char('n'), Char('r'), Char('t'), Char('v'), OctalDigit, Char('x')],
+};
+
+const DIALECTS: [&Dialect; 4] = [&AOC, &RUST, &JSON, &C];
+
+// Offsets are byte offsets into the line the literal was parsed from.
+#[derive(Debug, PartialEq)]
+enum LiteralError {
+    UnexpectedChar {
+        offset: usize,
+        found: char,
+        expected: &'static [Expected],
+    },
+    UnexpectedEnd {
+        offset: usize,
+        expected: &'static [Expected],
+    },
+    InvalidCodePoint {
+        offset: usize,
+        value: u32,
+    },
+    LoneSurrogate {
+        offset: usize,
+        value: u32,
+    },
+}
+
+enum ScanError {
+    Unexpected(&'static [Expected]),
+    InvalidCodePoint(u32),
+    LoneSurrogate(u32),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Escape {
+    Simple(char),
+    Hex,
+    // The number of digits, which is three whenever a digit follows.
+    Octal(usize),
+    UnicodeBraced,
+    UnicodeFourDigits,
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+struct LineStatistics {
+    code: usize,
+    memory: usize,
+    encoded: usize,
+}
+
+type IntermediateScanResult = Result<Option<char>, ScanError>;
+type ParseResult = Result<String, LiteralError>;
 
 fn read_file(path: &Path) -> Vec<String> {
     let mut input = String::new();
@@ -29,14 +182,14 @@ fn scan_no_string(state: &mut ParseState, c: char) -> IntermediateScanResult {
             *state = Normal;
             Ok(None)
         }
-        _ => Err(r#"Unknown symbol outside of String. Expected '"'."#),
+        _ => Err(Unexpected(QUOTE)),
     }
 }
 
 fn scan_normal(state: &mut ParseState, c: char) -> IntermediateScanResult {
     match c {
         '"' => {
-            *state = NoString;
+            *state = Finished;
             Ok(None)
         }
         '\\' => {
@@ -47,17 +200,29 @@ fn scan_normal(state: &mut ParseState, c: char) -> IntermediateScanResult {
     }
 }
 
-fn scan_escaped(state: &mut ParseState, c: char) -> IntermediateScanResult {
-    match c {
-        '"' | '\\' => {
-            *state = Normal;
-            Ok(Some(c))
+fn scan_escaped(state: &mut ParseState, c: char, dialect: &Dialect) -> IntermediateScanResult {
+    if let Some(&(_, decoded)) = dialect.escapes.iter().find(|&&(e, _)| e == c) {
+        *state = Normal;
+        return Ok(Some(decoded));
+    }
+    match (c, c.to_digit(8)) {
+        (_, Some(digit)) if dialect.octal => {
+            *state = EscapedOctal(digit, 1);
+            Ok(None)
         }
-        'x' => {
+        ('x', _) if dialect.max_hex.is_some() => {
             *state = EscapedHexadecimal;
             Ok(None)
         }
-        _ => Err(r#"Unknown Escape Sequence. Expected '"', '\' or 'x'."#),
+        ('u', _) if dialect.unicode == Unicode::Braced => {
+            *state = EscapedUnicode;
+            Ok(None)
+        }
+        ('u', _) if dialect.unicode == Unicode::FourDigits => {
+            *state = EscapedUnicodeFixed(None, 0, 0);
+            Ok(None)
+        }
+        _ => Err(Unexpected(dialect.expected)),
     }
 }
 
@@ -67,95 +232,537 @@ fn scan_escaped_hex(state: &mut ParseState, c: char) -> IntermediateScanResult {
             *state = EscapedHexadecimal1(c);
             Ok(None)
         }
-        _ => Err(r#"Unknown Hexadecimal Escape Sequence. Expected a digit."#),
+        _ => Err(Unexpected(HEX_DIGIT)),
     }
 }
 
-fn scan_escaped_hex1(state: &mut ParseState, c: char, digit: char) -> IntermediateScanResult {
+fn scan_escaped_hex1(state: &mut ParseState,
+                     c: char,
+                     digit: char,
+                     dialect: &Dialect)
+                     -> IntermediateScanResult {
     match c {
         '0'...'9' | 'A'...'F' | 'a'...'f' => {
             *state = Normal;
-            Ok(digit.to_digit(16)
-                    .and_then(|d1| c.to_digit(16).and_then(|d2| char::from_u32(d1 * 16 + d2))))
+            let value = digit.to_digit(16).unwrap() * 16 + c.to_digit(16).unwrap();
+            if dialect.max_hex.is_some_and(|max| value > max) {
+                return Err(InvalidCodePoint(value));
+            }
+            Ok(char::from_u32(value))
         }
-        _ => Err(r#"Unknown Hexadecimal Escape Sequence. Expected a digit."#),
+        _ => Err(Unexpected(HEX_DIGIT)),
+    }
+}
+
+// Only gets the digits of an octal escape, decode_with ends it early at any
+// other character.
+fn scan_escaped_octal(state: &mut ParseState,
+                      c: char,
+                      value: u32,
+                      digits: usize,
+                      dialect: &Dialect)
+                      -> IntermediateScanResult {
+    let value = value * 8 + c.to_digit(8).unwrap();
+    if digits < 2 {
+        *state = EscapedOctal(value, digits + 1);
+        return Ok(None);
+    }
+    *state = Normal;
+    if dialect.max_hex.is_some_and(|max| value > max) {
+        return Err(InvalidCodePoint(value));
     }
+    Ok(char::from_u32(value))
 }
 
+fn scan_escaped_unicode(state: &mut ParseState, c: char) -> IntermediateScanResult {
+    match c {
+        '{' => {
+            *state = EscapedUnicodeBraced(0, 0);
+            Ok(None)
+        }
+        _ => Err(Unexpected(OPEN_BRACE)),
+    }
+}
+
+fn scan_unicode_braced(state: &mut ParseState,
+                       c: char,
+                       value: u32,
+                       digits: usize)
+                       -> IntermediateScanResult {
+    match (c, c.to_digit(16)) {
+        ('}', _) if digits > 0 => {
+            *state = Normal;
+            char::from_u32(value).map(Some).ok_or(InvalidCodePoint(value))
+        }
+        (_, Some(digit)) if digits < 6 => {
+            *state = EscapedUnicodeBraced(value * 16 + digit, digits + 1);
+            Ok(None)
+        }
+        _ if digits == 0 => Err(Unexpected(HEX_DIGIT)),
+        _ if digits == 6 => Err(Unexpected(CLOSE_BRACE)),
+        _ => Err(Unexpected(HEX_DIGIT_OR_BRACE)),
+    }
+}
+
+fn scan_unicode_fixed(state: &mut ParseState,
+                      c: char,
+                      high: Option<u32>,
+                      value: u32,
+                      digits: usize)
+                      -> IntermediateScanResult {
+    let value = value * 16 + c.to_digit(16).ok_or(Unexpected(HEX_DIGIT))?;
+    if digits < 3 {
+        *state = EscapedUnicodeFixed(high, value, digits + 1);
+        return Ok(None);
+    }
+    *state = Normal;
+    match (high, value) {
+        (Some(high), 0xDC00..=0xDFFF) => {
+            let value = 0x10000 + ((high - 0xD800) << 10) + (value - 0xDC00);
+            char::from_u32(value).map(Some).ok_or(InvalidCodePoint(value))
+        }
+        (Some(high), _) => Err(LoneSurrogate(high)),
+        (None, 0xD800..=0xDBFF) => {
+            *state = HighSurrogate(value);
+            Ok(None)
+        }
+        (None, 0xDC00..=0xDFFF) => Err(LoneSurrogate(value)),
+        (None, _) => char::from_u32(value).map(Some).ok_or(InvalidCodePoint(value)),
+    }
+}
+
+// Anything but another \u escape leaves the high surrogate without its pair.
+fn scan_high_surrogate(state: &mut ParseState, c: char, high: u32) -> IntermediateScanResult {
+    match c {
+        '\\' => {
+            *state = HighSurrogateEscaped(high);
+            Ok(None)
+        }
+        _ => Err(LoneSurrogate(high)),
+    }
+}
+
+fn scan_high_surrogate_escaped(state: &mut ParseState,
+                               c: char,
+                               high: u32)
+                               -> IntermediateScanResult {
+    match c {
+        'u' => {
+            *state = EscapedUnicodeFixed(Some(high), 0, 0);
+            Ok(None)
+        }
+        _ => Err(LoneSurrogate(high)),
+    }
+}
+
+#[cfg(test)]
 fn parse_string(input: &str) -> ParseResult {
+    parse_string_with(input, &AOC)
+}
+
+fn parse_string_with(input: &str, dialect: &Dialect) -> ParseResult {
+    let mut parsed = String::new();
+    decode_with(input, dialect, |c| parsed.push(c))?;
+    Ok(parsed)
+}
+
+// Hands the decoded characters to `emit` one at a time, so they can be
+// counted without building the decoded string.
+fn decode_with<F>(input: &str, dialect: &Dialect, mut emit: F) -> Result<(), LiteralError>
+    where F: FnMut(char)
+{
     let mut error = None;
-    let parsed = input.chars()
-                      .into_iter()
-                      .scan(NoString, |state, c| {
-                          let result = match state {
-                              &mut NoString => scan_no_string(state, c),
-                              &mut Normal => scan_normal(state, c),
-                              &mut Escaped => scan_escaped(state, c),
-                              &mut EscapedHexadecimal => scan_escaped_hex(state, c),
-                              &mut EscapedHexadecimal1(digit) => scan_escaped_hex1(state, c, digit),
-                          };
-                          match result {
-                              Ok(inner) => Some(inner),
-                              Err(err) => {
-                                  error = Some(err);
-                                  None
-                              }
-                          }
-                      })
-                      .filter_map(|c| c)
-                      .collect();
-
-    match error {
-        Some(err) => Err(err),
-        None => Ok(parsed),
-    }
-}
-
-fn parse_strings<'a, I>(lines: I) -> Vec<ParseResult>
+    let mut final_state = NoString;
+    input.char_indices()
+         .scan(&mut final_state, |state, (offset, c)| {
+             // An octal escape with fewer than three digits ends right before
+             // the next character, which then gets scanned as usual.
+             let ended = match **state {
+                 EscapedOctal(value, _) if c.to_digit(8).is_none() => {
+                     **state = Normal;
+                     char::from_u32(value)
+                 }
+                 _ => None,
+             };
+             let result = match **state {
+                 NoString => scan_no_string(state, c),
+                 Normal => scan_normal(state, c),
+                 Escaped => scan_escaped(state, c, dialect),
+                 EscapedHexadecimal => scan_escaped_hex(state, c),
+                 EscapedHexadecimal1(digit) => {
+                     scan_escaped_hex1(state, c, digit, dialect)
+                 }
+                 EscapedOctal(value, digits) => {
+                     scan_escaped_octal(state, c, value, digits, dialect)
+                 }
+                 EscapedUnicode => scan_escaped_unicode(state, c),
+                 EscapedUnicodeBraced(value, digits) => {
+                     scan_unicode_braced(state, c, value, digits)
+                 }
+                 EscapedUnicodeFixed(high, value, digits) => {
+                     scan_unicode_fixed(state, c, high, value, digits)
+                 }
+                 HighSurrogate(high) => scan_high_surrogate(state, c, high),
+                 HighSurrogateEscaped(high) => scan_high_surrogate_escaped(state, c, high),
+                 Finished => Err(Unexpected(END)),
+             };
+             match result {
+                 Ok(inner) => Some(ended.into_iter().chain(inner)),
+                 Err(Unexpected(expected)) => {
+                     error = Some(LiteralError::UnexpectedChar {
+                         offset,
+                         found: c,
+                         expected,
+                     });
+                     None
+                 }
+                 Err(InvalidCodePoint(value)) => {
+                     error = Some(LiteralError::InvalidCodePoint {
+                         offset,
+                         value,
+                     });
+                     None
+                 }
+                 Err(LoneSurrogate(value)) => {
+                     error = Some(LiteralError::LoneSurrogate {
+                         offset,
+                         value,
+                     });
+                     None
+                 }
+             }
+         })
+         .flatten()
+         .for_each(&mut emit);
+
+    if let Some(err) = error {
+        return Err(err);
+    }
+
+    let expected = match final_state {
+        Finished => return Ok(()),
+        NoString | Normal | EscapedOctal(..) => QUOTE,
+        Escaped => dialect.expected,
+        EscapedHexadecimal | EscapedHexadecimal1(_) | EscapedUnicodeFixed(..) => HEX_DIGIT,
+        EscapedUnicode => OPEN_BRACE,
+        HighSurrogate(_) => BACKSLASH,
+        HighSurrogateEscaped(_) => UNICODE,
+        EscapedUnicodeBraced(_, 0) => HEX_DIGIT,
+        EscapedUnicodeBraced(_, 6) => CLOSE_BRACE,
+        EscapedUnicodeBraced(..) => HEX_DIGIT_OR_BRACE,
+    };
+    Err(LiteralError::UnexpectedEnd {
+        offset: input.len(),
+        expected,
+    })
+}
+
+fn parse_strings<'a, I>(lines: I, dialect: &Dialect) -> Vec<ParseResult>
     where I: IntoIterator<Item = &'a String>
 {
-    lines.into_iter().map(|l| parse_string(l)).collect()
+    lines.into_iter().map(|l| parse_string_with(l, dialect)).collect()
+}
+
+impl LiteralError {
+    fn offset(&self) -> usize {
+        match *self {
+            LiteralError::UnexpectedChar { offset, .. } |
+            LiteralError::UnexpectedEnd { offset, .. } |
+            LiteralError::InvalidCodePoint { offset, .. } |
+            LiteralError::LoneSurrogate { offset, .. } => offset,
+        }
+    }
+}
+
+impl fmt::Display for Expected {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Char(c) => write!(f, "'{}'", c),
+            HexDigit => write!(f, "a hexadecimal digit"),
+            OctalDigit => write!(f, "an octal digit"),
+            End => write!(f, "the end of the line"),
+        }
+    }
+}
+
+impl fmt::Display for LiteralError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let expected = match *self {
+            LiteralError::UnexpectedChar { offset, found, expected } => {
+                write!(f, "Unexpected '{}' at offset {}", found, offset)?;
+                expected
+            }
+            LiteralError::UnexpectedEnd { offset, expected } => {
+                write!(f, "Unexpected end of the line at offset {}", offset)?;
+                expected
+            }
+            LiteralError::InvalidCodePoint { offset, value } => {
+                return write!(f, "Escape ending at offset {} encodes the invalid character {:#X}",
+                              offset,
+                              value);
+            }
+            LiteralError::LoneSurrogate { offset, value } => {
+                return write!(f, "Surrogate {:#X} is missing its other half at offset {}",
+                              value,
+                              offset);
+            }
+        };
+
+        write!(f, ", expected ")?;
+        for (i, e) in expected.iter().enumerate() {
+            if i > 0 {
+                write!(f, "{}", if i + 1 == expected.len() { " or " } else { ", " })?;
+            }
+            write!(f, "{}", e)?;
+        }
+        Ok(())
+    }
 }
 
+#[cfg(test)]
 fn encode_string(input: &str) -> String {
-    ['"']
-        .iter()
-        .cloned()
-        .chain(input.chars().flat_map(|c| {
-            match c {
-                '\\' | '"' => vec!['\\', c],
-                _ => vec![c],
+    encode_string_with(input, &AOC)
+}
+
+impl Escape {
+    // The length of the escape for the value, without the backslash.
+    fn len(self, value: u32) -> usize {
+        match self {
+            Escape::Simple(_) => 1,
+            Escape::Hex => 3,
+            Escape::Octal(digits) => digits,
+            Escape::UnicodeBraced => {
+                let bits = 32 - value.leading_zeros() as usize;
+                3 + cmp::max(1, bits.div_ceil(4))
+            }
+            Escape::UnicodeFourDigits => 5,
+        }
+    }
+
+    fn write(self, value: u32, output: &mut String) {
+        match self {
+            Escape::Simple(e) => output.push(e),
+            Escape::Hex => write!(output, "x{:02x}", value).unwrap(),
+            Escape::Octal(digits) => write!(output, "{:01$o}", value, digits).unwrap(),
+            Escape::UnicodeBraced => write!(output, "u{{{:x}}}", value).unwrap(),
+            Escape::UnicodeFourDigits => write!(output, "u{:04x}", value).unwrap(),
+        }
+    }
+}
+
+// Everything but printable ASCII gets escaped, so the encoded literal
+// survives being written to a line of a file. Characters the dialect has no
+// escape for stay as they are, which the decoder accepts just as well.
+fn needs_escape(c: char) -> bool {
+    c == '"' || c == '\\' || !(' '..='~').contains(&c)
+}
+
+// Octal escapes go before hexadecimal ones, which are never shorter, so C
+// never sees a \xHH that its greedy parsing could run into the next
+// character with.
+fn shortest_escape(c: char, next: Option<char>, dialect: &Dialect) -> Option<Escape> {
+    let value = c as u32;
+    let simple = dialect.escapes.iter().find(|&&(_, d)| d == c).map(|&(e, _)| Escape::Simple(e));
+    let octal = dialect.max_hex.filter(|&max| dialect.octal && value <= max).map(|_| {
+        if next.is_some_and(|n| n.is_digit(8)) {
+            Escape::Octal(3)
+        } else {
+            let bits = 32 - value.leading_zeros() as usize;
+            Escape::Octal(cmp::max(1, bits.div_ceil(3)))
+        }
+    });
+    let hex = dialect.max_hex.filter(|&max| value <= max).map(|_| Escape::Hex);
+    let unicode = match dialect.unicode {
+        Unicode::Braced => Some(Escape::UnicodeBraced),
+        Unicode::FourDigits if value <= 0xFFFF => Some(Escape::UnicodeFourDigits),
+        _ => None,
+    };
+    [simple, octal, hex, unicode].iter().flatten().cloned().min_by_key(|e| e.len(value))
+}
+
+// Each character along with the one after it, which decides whether an
+// escape may end with fewer digits.
+fn with_next(input: &str) -> impl Iterator<Item = (char, Option<char>)> + '_ {
+    input.chars().zip(input.chars().skip(1).map(Some).chain(Some(None)))
+}
+
+fn encode_string_with(input: &str, dialect: &Dialect) -> String {
+    let mut encoded = String::from("\"");
+    for (c, next) in with_next(input) {
+        match shortest_escape(c, next, dialect).filter(|_| needs_escape(c)) {
+            Some(escape) => {
+                encoded.push('\\');
+                escape.write(c as u32, &mut encoded);
             }
-        }))
-        .chain(['"'].iter().cloned())
-        .collect()
+            None => encoded.push(c),
+        }
+    }
+    encoded.push('"');
+    encoded
+}
+
+// The number of characters encode_string_with would produce.
+fn encoded_length(input: &str, dialect: &Dialect) -> usize {
+    2 + with_next(input)
+             .map(|(c, next)| {
+                 match shortest_escape(c, next, dialect).filter(|_| needs_escape(c)) {
+                     Some(escape) => 1 + escape.len(c as u32),
+                     None => 1,
+                 }
+             })
+             .sum::<usize>()
 }
 
-fn encode_strings<'a, I>(lines: I) -> Vec<String>
+fn encode_strings<'a, I>(lines: I, dialect: &Dialect) -> Vec<String>
     where I: IntoIterator<Item = &'a String>
 {
-    lines.into_iter().map(|l| encode_string(l)).collect()
+    lines.into_iter().map(|l| encode_string_with(l, dialect)).collect()
+}
+
+fn line_statistics(line: &str, dialect: &Dialect) -> Result<LineStatistics, LiteralError> {
+    let mut memory = 0;
+    decode_with(line, dialect, |_| memory += 1)?;
+    Ok(LineStatistics {
+        code: line.chars().count(),
+        memory,
+        encoded: encoded_length(line, dialect),
+    })
+}
+
+// Reads one line at a time into the same buffer, so only the longest line
+// ever has to fit into memory. Returns the totals of all the valid lines.
+fn stream_statistics<R, F>(mut reader: R,
+                           dialect: &Dialect,
+                           mut on_line: F)
+                           -> io::Result<LineStatistics>
+    where R: BufRead,
+          F: FnMut(usize, &str, Result<LineStatistics, LiteralError>)
+{
+    let mut total = LineStatistics::default();
+    let mut line = String::new();
+    let mut number = 0;
+    while reader.read_line(&mut line)? > 0 {
+        number += 1;
+        let trimmed = line.trim_end_matches(['\n', '\r']);
+        let statistics = line_statistics(trimmed, dialect);
+        if let Ok(statistics) = statistics {
+            total.code += statistics.code;
+            total.memory += statistics.memory;
+            total.encoded += statistics.encoded;
+        }
+        on_line(number, trimmed, statistics);
+        line.clear();
+    }
+    Ok(total)
 }
 
 fn main() {
-    let lines = read_file(Path::new("input.txt"));
-    let parsed = parse_strings(&lines);
+    let arguments = args().skip(1).collect::<Vec<_>>();
+    let stream = arguments.iter().any(|a| a == "--stream");
+    let dialect = arguments.iter()
+                           .position(|a| a == "--dialect")
+                           .and_then(|i| arguments.get(i + 1))
+                           .map_or("aoc", |d| d.as_str());
+
+    let report = |dialect: &Dialect, list_errors: bool| {
+        if stream {
+            print_streamed_statistics(Path::new("input.txt"), dialect, list_errors);
+        } else {
+            let lines = read_file(Path::new("input.txt"));
+            print_statistics(&lines, dialect, list_errors);
+        }
+    };
+
+    if dialect == "all" {
+        for dialect in &DIALECTS {
+            println!("Dialect: {}", dialect.name);
+            report(dialect, false);
+            println!();
+        }
+    } else if let Some(dialect) = DIALECTS.iter().find(|d| d.name == dialect) {
+        report(dialect, true);
+    } else {
+        let names = DIALECTS.iter().map(|d| d.name).collect::<Vec<_>>();
+        println!("Unknown dialect '{}', expected one of {} or all", dialect, names.join(", "));
+    }
+}
+
+fn print_streamed_statistics(path: &Path, dialect: &Dialect, list_errors: bool) {
+    let file = File::open(path).expect("File could not be found");
+    let mut invalid = 0;
+    let on_line = |number, line: &str, statistics: Result<LineStatistics, LiteralError>| {
+        match statistics {
+            Ok(ref s) if list_errors => {
+                println!("Line {}: {} code, {} in memory, {} encoded",
+                         number,
+                         s.code,
+                         s.memory,
+                         s.encoded)
+            }
+            Ok(_) => {}
+            Err(ref err) => {
+                invalid += 1;
+                if list_errors {
+                    print_error(number, line, err);
+                }
+            }
+        }
+    };
+    let total = stream_statistics(BufReader::new(file), dialect, on_line)
+                    .expect("File could not be read");
+
+    if invalid > 0 {
+        println!("Skipped {} invalid lines", invalid);
+    }
+    println!("Input Chars: {}", total.code);
+    println!("Parsed Chars: {}", total.memory);
+    println!("Overhead: {}", total.code - total.memory);
+    println!("Encoded Chars: {}", total.encoded);
+    println!("Overhead: {}", total.encoded - total.code);
+}
+
+fn print_statistics(lines: &[String], dialect: &Dialect, list_errors: bool) {
+    let parsed = parse_strings(lines, dialect);
+
+    let mut valid_lines = Vec::new();
+    let mut parsed_lines = Vec::new();
+    for (number, (line, result)) in lines.iter().zip(parsed).enumerate() {
+        match result {
+            Ok(parsed) => {
+                valid_lines.push(line);
+                parsed_lines.push(parsed);
+            }
+            Err(ref err) if list_errors => print_error(number + 1, line, err),
+            Err(_) => {}
+        }
+    }
+    if valid_lines.len() < lines.len() {
+        println!("Skipped {} invalid lines", lines.len() - valid_lines.len());
+    }
+    let lines = valid_lines;
 
     let input_chars = lines.iter().fold(0, |a, l| a + l.chars().count());
-    let parsed_chars = parsed.into_iter()
-                             .fold(0, |a, r| a + r.ok().map(|l| l.chars().count()).unwrap_or(0));
+    let parsed_chars = parsed_lines.iter().fold(0, |a, l| a + l.chars().count());
 
     println!("Input Chars: {}", input_chars);
     println!("Parsed Chars: {}", parsed_chars);
     println!("Overhead: {}", input_chars - parsed_chars);
 
-    let encoded = encode_strings(&lines);
+    let encoded = encode_strings(lines, dialect);
     let encoded_chars = encoded.iter().fold(0, |a, l| a + l.chars().count());
 
     println!("Encoded Chars: {}", encoded_chars);
     println!("Overhead: {}", encoded_chars - input_chars);
 }
 
+// Shows the line with a caret under the character the error points at.
+fn print_error(number: usize, line: &str, err: &LiteralError) {
+    let column = line[..err.offset()].chars().count();
+    println!("Line {}: {}", number, err);
+    println!("    {}", line);
+    println!("    {}^", " ".repeat(column));
+}
+
 #[test]
 fn test_parsing() {
     assert_eq!(parse_string(r#""""#), Ok(r#""#.to_owned()));
@@ -178,3 +785,242 @@ fn test_encoding() {
     assert_eq!(encode_string(r#""aaa\\aaa""#), r#""\"aaa\\\\aaa\"""#);
     assert_eq!(encode_string(r#""\x27""#), r#""\"\\x27\"""#);
 }
+
+#[test]
+fn test_errors() {
+    assert_eq!(parse_string(r#"a"#),
+               Err(LiteralError::UnexpectedChar {
+                   offset: 0,
+                   found: 'a',
+                   expected: QUOTE,
+               }));
+    assert_eq!(parse_string(r#""abc"a"#),
+               Err(LiteralError::UnexpectedChar {
+                   offset: 5,
+                   found: 'a',
+                   expected: END,
+               }));
+    assert_eq!(parse_string(r#""aaa\aaa""#),
+               Err(LiteralError::UnexpectedChar {
+                   offset: 5,
+                   found: 'a',
+                   expected: ESCAPE,
+               }));
+    assert_eq!(parse_string(r#""é\xg7""#),
+               Err(LiteralError::UnexpectedChar {
+                   offset: 5,
+                   found: 'g',
+                   expected: HEX_DIGIT,
+               }));
+    assert_eq!(parse_string(r#""abc"#),
+               Err(LiteralError::UnexpectedEnd {
+                   offset: 4,
+                   expected: QUOTE,
+               }));
+    assert_eq!(parse_string(r#""\x4"#),
+               Err(LiteralError::UnexpectedEnd {
+                   offset: 4,
+                   expected: HEX_DIGIT,
+               }));
+    assert_eq!(parse_string(r#""aaa\aaa""#).unwrap_err().to_string(),
+               r#"Unexpected 'a' at offset 5, expected '"', '\' or 'x'"#);
+    assert_eq!(parse_string(r#""abc"#).unwrap_err().to_string(),
+               r#"Unexpected end of the line at offset 4, expected '"'"#);
+}
+
+#[test]
+fn test_dialects() {
+    assert_eq!(parse_string_with(r#""a\tb\n\u{1F600}\x41\'""#, &RUST),
+               Ok("a\tb\n\u{1F600}A'".to_owned()));
+    assert_eq!(parse_string_with(r#""\/\bé\"""#, &JSON), Ok("/\u{8}é\"".to_owned()));
+    assert_eq!(parse_string_with(r#""\a\?\xff\v""#, &C), Ok("\u{7}?\u{ff}\u{b}".to_owned()));
+
+    assert_eq!(parse_string_with(r#""\n""#, &AOC),
+               Err(LiteralError::UnexpectedChar {
+                   offset: 2,
+                   found: 'n',
+                   expected: ESCAPE,
+               }));
+    assert_eq!(parse_string_with(r#""\x80""#, &RUST),
+               Err(LiteralError::InvalidCodePoint {
+                   offset: 4,
+                   value: 0x80,
+               }));
+    assert_eq!(parse_string_with(r#""\u{D800}""#, &RUST),
+               Err(LiteralError::InvalidCodePoint {
+                   offset: 8,
+                   value: 0xD800,
+               }));
+    assert_eq!(parse_string_with(r#""\u{1234567}""#, &RUST),
+               Err(LiteralError::UnexpectedChar {
+                   offset: 10,
+                   found: '7',
+                   expected: CLOSE_BRACE,
+               }));
+    assert_eq!(parse_string_with(r#""\u{12"#, &RUST),
+               Err(LiteralError::UnexpectedEnd {
+                   offset: 6,
+                   expected: HEX_DIGIT_OR_BRACE,
+               }));
+    assert_eq!(parse_string_with(r#""\ud83d\ude00\u00e9""#, &JSON),
+               Ok("\u{1F600}é".to_owned()));
+    assert_eq!(parse_string_with(r#""\ud83d""#, &JSON),
+               Err(LiteralError::LoneSurrogate {
+                   offset: 7,
+                   value: 0xD83D,
+               }));
+    assert_eq!(parse_string_with(r#""\ud83d\n""#, &JSON),
+               Err(LiteralError::LoneSurrogate {
+                   offset: 8,
+                   value: 0xD83D,
+               }));
+    assert_eq!(parse_string_with(r#""\ud83d\u0041""#, &JSON),
+               Err(LiteralError::LoneSurrogate {
+                   offset: 12,
+                   value: 0xD83D,
+               }));
+    assert_eq!(parse_string_with(r#""\ude00""#, &JSON).unwrap_err().to_string(),
+               "Surrogate 0xDE00 is missing its other half at offset 6");
+    assert_eq!(parse_string_with(r#""\ud83d"#, &JSON),
+               Err(LiteralError::UnexpectedEnd {
+                   offset: 7,
+                   expected: BACKSLASH,
+               }));
+    assert_eq!(parse_string_with(r#""\u12""#, &JSON).unwrap_err().to_string(),
+               r#"Unexpected '"' at offset 5, expected a hexadecimal digit"#);
+    assert_eq!(parse_string_with(r#""\x41""#, &JSON).unwrap_err().to_string(),
+               "Unexpected 'x' at offset 2, expected '\"', '\\', '/', 'b', 'f', 'n', 'r', 't' \
+                or 'u'");
+}
+
+#[test]
+fn test_dialect_expectations() {
+    for dialect in &DIALECTS {
+        let mut expected = dialect.escapes.iter().map(|&(e, _)| Char(e)).collect::<Vec<_>>();
+        if dialect.octal {
+            expected.push(OctalDigit);
+        }
+        if dialect.max_hex.is_some() {
+            expected.push(Char('x'));
+        }
+        if dialect.unicode != Unicode::Unsupported {
+            expected.push(Char('u'));
+        }
+        assert_eq!(dialect.expected, expected.as_slice(), "{}", dialect.name);
+    }
+}
+
+#[test]
+fn test_dialect_round_trips() {
+    let literals: [(&Dialect, &[&str]); 4] =
+        [(&AOC, &[r#""""#, r#""a\"b\\c""#, r#""\x27\x00\x7f\xfe""#]),
+         (&RUST, &[r#""tab\there""#, r#""\x27\x00\x7f""#, r#""\u{e9}\u{1F600}\n\r\0""#]),
+         (&JSON, &[r#""é\b\f\/""#, r#""\u00e9\ud83d\ude00\t""#]),
+         (&C, &[r#""\a\v\?\xfe""#, r#""tab\there\0""#, r#""\101\0011\7\08""#])];
+    for &(dialect, literals) in &literals {
+        for literal in literals {
+            let decoded = parse_string_with(literal, dialect).unwrap();
+            let encoded = encode_string_with(&decoded, dialect);
+            assert_eq!(parse_string_with(&encoded, dialect), Ok(decoded), "{}", dialect.name);
+        }
+    }
+    assert_eq!(encode_string_with("a\tb\u{7}\"", &RUST), r#""a\tb\x07\"""#);
+    assert_eq!(encode_string_with("a\tb\u{7}", &C), r#""a\tb\a""#);
+}
+
+#[test]
+fn test_c_escapes() {
+    assert_eq!(parse_string_with(r#""\101\60\0a\1234""#, &C), Ok("A0\0aS4".to_owned()));
+    assert_eq!(parse_string_with(r#""\400""#, &C),
+               Err(LiteralError::InvalidCodePoint {
+                   offset: 4,
+                   value: 0o400,
+               }));
+    assert_eq!(parse_string_with(r#""\8""#, &C).unwrap_err().to_string(),
+               "Unexpected '8' at offset 2, expected '\"', '\\', ''', '?', 'a', 'b', 'f', 'n', \
+                'r', 't', 'v', an octal digit or 'x'");
+
+    // Neither an octal escape nor C's greedy hexadecimal escape may run into
+    // the character after it.
+    assert_eq!(encode_string_with("\u{0}1", &C), r#""\0001""#);
+    assert_eq!(encode_string_with("\u{0}8", &C), r#""\08""#);
+    assert_eq!(encode_string_with("\u{fe}a", &C), r#""\376a""#);
+    for input in &["\u{0}1", "\u{fe}a", "\u{0}8", "\u{7}7\u{1f}", "\u{1}\u{2}"] {
+        let encoded = encode_string_with(input, &C);
+        assert_eq!(parse_string_with(&encoded, &C), Ok(input.to_string()));
+        assert_eq!(encoded_length(input, &C), encoded.chars().count());
+    }
+}
+
+#[test]
+fn test_encoding_non_printables() {
+    assert_eq!(encode_string("\u{0}\u{7f}é€\n"), r#""\x00\x7f\xe9€\x0a""#);
+    assert_eq!(encode_string_with("\u{1}\n\u{1F600}", &JSON), "\"\\u0001\\n\u{1F600}\"");
+    assert_eq!(encode_string_with("\u{80}\u{1F600}\0", &RUST), r#""\u{80}\u{1f600}\0""#);
+    assert_eq!(encode_string_with("\u{b}\u{9f}", &C), r#""\v\237""#);
+}
+
+#[test]
+fn test_encoding_round_trips() {
+    let mut state = 0x2545_F491_4F6C_DD1D_u64;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+
+    for _ in 0..5000 {
+        let length = next() % 12;
+        let input = (0..length)
+                        .filter_map(|_| {
+                            let random = next();
+                            let mask = [0x7F, 0xFF, 0xFFFF, 0x1F_FFFF][random as usize % 4];
+                            char::from_u32((random >> 8) as u32 & mask)
+                        })
+                        .collect::<String>();
+
+        assert_eq!(parse_string(&encode_string(&input)), Ok(input.clone()));
+        for dialect in &DIALECTS {
+            let encoded = encode_string_with(&input, dialect);
+            assert!(!encoded.contains(|c: char| c.is_control()), "{:?}", encoded);
+            assert_eq!(parse_string_with(&encoded, dialect), Ok(input.clone()));
+        }
+    }
+}
+
+#[test]
+fn test_streaming_statistics() {
+    let input = "\"\"\n\"abc\"\r\n\"aaa\\\"aaa\"\n\"\\x27\"\n\"\\q\"\n";
+    let mut lines = Vec::new();
+    let on_line = |number, line: &str, statistics: Result<LineStatistics, LiteralError>| {
+        lines.push((number, line.to_owned(), statistics.ok()))
+    };
+    let total = stream_statistics(input.as_bytes(), &AOC, on_line).unwrap();
+
+    let statistics = |code, memory, encoded| {
+        Some(LineStatistics {
+            code,
+            memory,
+            encoded,
+        })
+    };
+    assert_eq!(lines,
+               vec![(1, r#""""#.to_owned(), statistics(2, 0, 6)),
+                    (2, r#""abc""#.to_owned(), statistics(5, 3, 9)),
+                    (3, r#""aaa\"aaa""#.to_owned(), statistics(10, 7, 16)),
+                    (4, r#""\x27""#.to_owned(), statistics(6, 1, 11)),
+                    (5, r#""\q""#.to_owned(), None)]);
+    assert_eq!(total, statistics(23, 11, 42).unwrap());
+}
+
+#[test]
+fn test_encoded_length() {
+    let inputs = ["", "abc", "a\"b\\", "\u{0}\u{7f}é€\n", "\u{1}\u{1F600}\u{80}\u{10FFFF}"];
+    for dialect in &DIALECTS {
+        for input in &inputs {
+            assert_eq!(encoded_length(input, dialect),
+                       encode_string_with(input, dialect).chars().count());
+        }
+    }
+}
@@ -0,0 +1,527 @@
+use std::cmp;
+
+use {Graph, Objective, Query, Route};
+
+// Missing connections get a prohibitive weight instead of being ruled out,
+// so that the local searches can still move away from them.
+const MISSING: i64 = 1 << 40;
+const ANNEALING_STEPS_PER_CITY: usize = 1000;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Heuristic {
+    NearestNeighbour,
+    TwoOpt,
+    OrOpt,
+    Annealing(u64),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Estimate {
+    pub route: Route,
+    // The optimum is at least this long for the shortest route and at most
+    // this long for the longest one.
+    pub bound: Option<usize>,
+}
+
+// xorshift64*, seeded through splitmix64 so that every seed works.
+struct Random(u64);
+
+impl Random {
+    fn new(seed: u64) -> Self {
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        Random((z ^ (z >> 31)) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next() % bound as u64) as usize
+    }
+
+    fn unit(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+// A route as the list of cities it passes, including the start again at the
+// end of round trips. Everything outside of first..=last stays in place, so
+// fixed endpoints are kept. All searches minimize the weight, which is the
+// negated distance when looking for the longest route.
+struct Search<'a> {
+    graph: &'a Graph,
+    objective: Objective,
+    path: Vec<usize>,
+    first: usize,
+    last: usize,
+}
+
+impl<'a> Search<'a> {
+    fn new(graph: &'a Graph, objective: Objective, path: Vec<usize>, pinned: (bool, bool)) -> Self {
+        let first = if pinned.0 { 1 } else { 0 };
+        let last = if pinned.1 {
+            path.len().saturating_sub(2)
+        } else {
+            path.len() - 1
+        };
+        Search {
+            graph,
+            objective,
+            path,
+            first,
+            last,
+        }
+    }
+
+    // Connections to positions outside of the path don't cost anything.
+    fn weight(&self, from: Option<usize>, to: Option<usize>) -> i64 {
+        match (from, to) {
+            (Some(from), Some(to)) => self.graph.weight(self.objective, from, to),
+            _ => 0,
+        }
+    }
+
+    fn before(&self, position: usize) -> Option<usize> {
+        if position == 0 {
+            None
+        } else {
+            Some(self.path[position - 1])
+        }
+    }
+
+    fn after(&self, position: usize) -> Option<usize> {
+        self.path.get(position + 1).cloned()
+    }
+
+    fn total(&self) -> i64 {
+        self.path.windows(2).map(|leg| self.weight(Some(leg[0]), Some(leg[1]))).sum()
+    }
+
+    fn reversal_delta(&self, i: usize, j: usize) -> i64 {
+        let (head, tail) = (Some(self.path[i]), Some(self.path[j]));
+        let (before, after) = (self.before(i), self.after(j));
+        let inner = (i..j)
+                        .map(|t| {
+                            let (a, b) = (Some(self.path[t]), Some(self.path[t + 1]));
+                            self.weight(b, a) - self.weight(a, b)
+                        })
+                        .sum::<i64>();
+        self.weight(before, tail) + self.weight(head, after) - self.weight(before, head) -
+        self.weight(tail, after) + inner
+    }
+
+    // The change in weight from moving path[i..=end] into the gap before
+    // position gap, which must not be next to the run.
+    fn move_delta(&self, i: usize, end: usize, gap: usize) -> i64 {
+        let (head, tail) = (Some(self.path[i]), Some(self.path[end]));
+        let (before, after) = (self.before(i), self.after(end));
+        let (left, right) = (self.before(gap), self.path.get(gap).cloned());
+        self.weight(left, head) + self.weight(tail, right) - self.weight(left, right) -
+        self.weight(before, head) - self.weight(tail, after) + self.weight(before, after)
+    }
+
+    fn apply_move(&mut self, i: usize, end: usize, gap: usize) {
+        let segment = self.path.drain(i..end + 1).collect::<Vec<_>>();
+        let at = if gap > end { gap - segment.len() } else { gap };
+        let rest = self.path.split_off(at);
+        self.path.extend(segment);
+        self.path.extend(rest);
+    }
+
+    // Reverses parts of the route as long as that helps. The prefix sums of
+    // the legs in both directions make each candidate O(1) to evaluate,
+    // even when the distances aren't symmetric.
+    fn two_opt(&mut self) -> bool {
+        let mut improved = false;
+        loop {
+            let mut forward = vec![0];
+            let mut backward = vec![0];
+            for leg in self.path.windows(2) {
+                let (a, b) = (Some(leg[0]), Some(leg[1]));
+                let (f, r) = (*forward.last().unwrap(), *backward.last().unwrap());
+                forward.push(f + self.weight(a, b));
+                backward.push(r + self.weight(b, a));
+            }
+
+            let mut reversal = None;
+            'search: for i in self.first..self.last {
+                for j in i + 1..self.last + 1 {
+                    let (head, tail) = (Some(self.path[i]), Some(self.path[j]));
+                    let (before, after) = (self.before(i), self.after(j));
+                    let old = self.weight(before, head) + self.weight(tail, after) + forward[j] -
+                              forward[i];
+                    let new = self.weight(before, tail) + self.weight(head, after) + backward[j] -
+                              backward[i];
+                    if new < old {
+                        reversal = Some((i, j));
+                        break 'search;
+                    }
+                }
+            }
+
+            match reversal {
+                Some((i, j)) => {
+                    self.path[i..j + 1].reverse();
+                    improved = true;
+                }
+                None => return improved,
+            }
+        }
+    }
+
+    // Moves runs of up to three cities to a different place in the route.
+    fn or_opt(&mut self) -> bool {
+        let mut improved = false;
+        'restart: loop {
+            for length in 1..4 {
+                for i in self.first..self.last + 1 {
+                    let end = i + length - 1;
+                    if end > self.last {
+                        break;
+                    }
+                    // Gap g lies between the positions g - 1 and g.
+                    for gap in (self.first..self.last + 2).filter(|&g| g < i || g > end + 1) {
+                        if self.move_delta(i, end, gap) < 0 {
+                            self.apply_move(i, end, gap);
+                            improved = true;
+                            continue 'restart;
+                        }
+                    }
+                }
+            }
+            return improved;
+        }
+    }
+
+    fn improve(&mut self) {
+        while self.two_opt() || self.or_opt() {}
+    }
+
+    // Random reversals and moves that are accepted even when they make the
+    // route worse, with a probability that drops as the temperature cools
+    // down. Moves matter for directed graphs, where reversals rarely help.
+    fn anneal(&mut self, random: &mut Random) {
+        let span = (self.last + 1).saturating_sub(self.first);
+        if span < 2 {
+            return;
+        }
+
+        // Starts out hot enough to accept a typical connection getting worse.
+        let distances = self.graph
+                            .distances
+                            .iter()
+                            .flat_map(|row| row.iter().filter_map(|&d| d))
+                            .collect::<Vec<_>>();
+        let mean = distances.iter().sum::<usize>() / cmp::max(1, distances.len());
+        let mut temperature = cmp::max(1, mean) as f64;
+        let steps = ANNEALING_STEPS_PER_CITY * self.path.len();
+        let cooling = 0.001f64.powf(1.0 / steps as f64);
+
+        let mut current = self.total();
+        let mut best = (current, self.path.clone());
+        for _ in 0..steps {
+            temperature *= cooling;
+            let (a, b) = (self.first + random.below(span), self.first + random.below(span + 1));
+            let reversal = random.next() & 1 == 0;
+            let end = cmp::min(a + random.below(3), self.last);
+            let delta = if reversal && a < b && b <= self.last {
+                self.reversal_delta(a, b)
+            } else if !reversal && (b < a || b > end + 1) {
+                self.move_delta(a, end, b)
+            } else {
+                continue;
+            };
+
+            if delta <= 0 || random.unit() < (-delta as f64 / temperature).exp() {
+                if reversal {
+                    self.path[a..b + 1].reverse();
+                } else {
+                    self.apply_move(a, end, b);
+                }
+                current += delta;
+                if current < best.0 {
+                    best = (current, self.path.clone());
+                }
+            }
+        }
+        self.path = best.1;
+    }
+}
+
+// The best connection into or out of every city, combined into a bound. A
+// path leaves every city except its end and enters every city except its
+// start, so without a fixed endpoint the city contributing the most is left
+// out. Cities that have no connection at all can only be that endpoint.
+fn side_bound(objective: Objective,
+              best: &[Option<usize>],
+              pinned: Option<usize>,
+              round_trip: bool)
+              -> Option<usize> {
+    if best.len() <= 1 {
+        return Some(0);
+    }
+    if round_trip {
+        return best.iter().cloned().sum();
+    }
+    if let Some(pinned) = pinned {
+        return best.iter().enumerate().filter(|&(city, _)| city != pinned).map(|(_, &b)| b).sum();
+    }
+
+    let known = best.iter().filter_map(|&b| b).collect::<Vec<_>>();
+    match best.len() - known.len() {
+        0 => {
+            let skipped = match objective {
+                Objective::Shortest => known.iter().max(),
+                Objective::Longest => known.iter().min(),
+            };
+            Some(known.iter().sum::<usize>() - skipped.unwrap())
+        }
+        1 => Some(known.iter().sum()),
+        _ => None,
+    }
+}
+
+impl Graph {
+    fn weight(&self, objective: Objective, from: usize, to: usize) -> i64 {
+        match (self.distances[from][to], objective) {
+            (Some(distance), Objective::Shortest) => distance as i64,
+            (Some(distance), Objective::Longest) => -(distance as i64),
+            (None, _) => MISSING,
+        }
+    }
+
+    fn bound(&self,
+             objective: Objective,
+             (start, end): (Option<usize>, Option<usize>),
+             round_trip: bool)
+             -> Option<usize> {
+        let count = self.names.len();
+        let best = |connections: &dyn Fn(usize) -> Vec<usize>| {
+            (0..count)
+                .map(|city| {
+                    connections(city).into_iter().fold(None, |best, distance| {
+                        Some(best.map_or(distance, |best| objective.pick(best, distance)))
+                    })
+                })
+                .collect::<Vec<_>>()
+        };
+        let incoming = best(&|to| (0..count).filter_map(|from| self.distances[from][to]).collect());
+        let outgoing = best(&|from| self.distances[from].iter().filter_map(|&d| d).collect());
+
+        let incoming = side_bound(objective, &incoming, start, round_trip);
+        let outgoing = side_bound(objective, &outgoing, end, round_trip);
+        match (incoming, outgoing, objective) {
+            (Some(a), Some(b), Objective::Shortest) => Some(cmp::max(a, b)),
+            (Some(a), Some(b), Objective::Longest) => Some(cmp::min(a, b)),
+            (a, b, _) => a.or(b),
+        }
+    }
+
+    // Greedily follows the best connection, trying every possible start.
+    fn nearest_neighbour(&self,
+                         objective: Objective,
+                         (start, end): (Option<usize>, Option<usize>),
+                         round_trip: bool)
+                         -> Option<Search<'_>> {
+        let count = self.names.len();
+        let pinned = (start.is_some() || round_trip, end.is_some() || round_trip);
+        let mut best: Option<Search> = None;
+
+        for first in (0..count).filter(|&c| start.is_none_or(|s| s == c)) {
+            if count > 1 && end == Some(first) {
+                continue;
+            }
+            let mut visited = vec![false; count];
+            visited[first] = true;
+            if let Some(end) = end {
+                visited[end] = true;
+            }
+
+            let mut path = vec![first];
+            let mut current = first;
+            while let Some(next) = (0..count)
+                                       .filter(|&city| !visited[city])
+                                       .min_by_key(|&city| self.weight(objective, current, city)) {
+                visited[next] = true;
+                path.push(next);
+                current = next;
+            }
+            match end {
+                Some(end) if end != first => path.push(end),
+                _ => {}
+            }
+            if round_trip && count > 1 {
+                path.push(first);
+            }
+
+            let search = Search::new(self, objective, path, pinned);
+            if best.as_ref().is_none_or(|best| search.total() < best.total()) {
+                best = Some(search);
+            }
+        }
+        best
+    }
+
+    pub fn estimate_route(&self,
+                          query: &Query,
+                          heuristic: Heuristic)
+                          -> Result<Option<Estimate>, String> {
+        let endpoints = self.get_endpoints(query)?;
+        let search = self.nearest_neighbour(query.objective, endpoints, query.round_trip);
+        let mut search = match search {
+            Some(search) => search,
+            None => return Ok(None),
+        };
+
+        match heuristic {
+            Heuristic::NearestNeighbour => {}
+            Heuristic::TwoOpt => {
+                search.two_opt();
+            }
+            Heuristic::OrOpt => {
+                search.or_opt();
+            }
+            Heuristic::Annealing(seed) => {
+                search.anneal(&mut Random::new(seed));
+                search.improve();
+            }
+        }
+
+        if search.path.windows(2).any(|leg| self.distances[leg[0]][leg[1]].is_none()) {
+            return Ok(None);
+        }
+        Ok(Some(Estimate {
+            route: self.to_route(&search.path),
+            bound: self.bound(query.objective, endpoints, query.round_trip),
+        }))
+    }
+}
+
+#[cfg(test)]
+const HEURISTICS: [Heuristic; 4] = [Heuristic::NearestNeighbour,
+                                    Heuristic::TwoOpt,
+                                    Heuristic::OrOpt,
+                                    Heuristic::Annealing(7)];
+
+#[cfg(test)]
+fn random_graph(random: &mut Random, count: usize, directed: bool) -> Graph {
+    let mut graph = Graph::new();
+    for a in 0..count {
+        for b in 0..count {
+            if a != b && (directed || a < b) {
+                let distance = 1 + random.below(100);
+                graph.add_directed_connection(&a.to_string(), &b.to_string(), distance);
+                if !directed {
+                    graph.add_directed_connection(&b.to_string(), &a.to_string(), distance);
+                }
+            }
+        }
+    }
+    graph
+}
+
+#[cfg(test)]
+fn check_estimates(graph: &Graph, query: &Query, directed: bool) {
+    let optimum = graph.find_routes(query).unwrap().routes[0].distance();
+    let nearest = graph.estimate_route(query, Heuristic::NearestNeighbour)
+                       .unwrap()
+                       .unwrap()
+                       .route
+                       .distance();
+    for &heuristic in &HEURISTICS {
+        let estimate = graph.estimate_route(query, heuristic).unwrap().unwrap();
+        let (route, bound) = (estimate.route, estimate.bound.unwrap());
+        assert_eq!(route.cities.len(), graph.names.len() + query.round_trip as usize);
+        match query.objective {
+            Objective::Shortest => assert!(bound <= optimum && optimum <= route.distance()),
+            Objective::Longest => assert!(bound >= optimum && optimum >= route.distance()),
+        }
+        // Annealing doesn't always find the optimum of directed graphs, but it
+        // never ends up worse than where it started.
+        if let Heuristic::Annealing(_) = heuristic {
+            if directed {
+                match query.objective {
+                    Objective::Shortest => assert!(route.distance() <= nearest),
+                    Objective::Longest => assert!(route.distance() >= nearest),
+                }
+            } else {
+                assert_eq!(route.distance(), optimum);
+            }
+        }
+        if let Some(start) = query.start {
+            assert_eq!(route.cities[0], start);
+        }
+        if let Some(end) = query.end.filter(|_| !query.round_trip) {
+            assert_eq!(route.cities.last().unwrap(), end);
+        }
+        if query.round_trip {
+            assert_eq!(route.cities.first(), route.cities.last());
+        }
+    }
+}
+
+#[test]
+fn test_heuristics_against_exact_search() {
+    let mut random = Random::new(42);
+    for round in 0..12 {
+        let directed = round % 2 == 1;
+        let graph = random_graph(&mut random, 8, directed);
+        for &objective in &[Objective::Shortest, Objective::Longest] {
+            check_estimates(&graph, &Query::new(objective), directed);
+            check_estimates(&graph,
+                            &Query { round_trip: true, ..Query::new(objective) },
+                            directed);
+            check_estimates(&graph,
+                            &Query {
+                                start: Some("3"),
+                                end: Some("5"),
+                                ..Query::new(objective)
+                            },
+                            directed);
+        }
+    }
+}
+
+#[test]
+fn test_heuristics_on_a_line() {
+    let mut random = Random::new(1);
+    let mut positions = (0..150).collect::<Vec<usize>>();
+    for i in (1..positions.len()).rev() {
+        positions.swap(i, random.below(i + 1));
+    }
+
+    let mut graph = Graph::new();
+    for (a, &x) in positions.iter().enumerate() {
+        for (b, &y) in positions.iter().enumerate().skip(a + 1) {
+            let distance = cmp::max(x, y) - cmp::min(x, y);
+            graph.add_connection(&a.to_string(), &b.to_string(), distance);
+        }
+    }
+
+    let query = Query::new(Objective::Shortest);
+    for &heuristic in &HEURISTICS[..3] {
+        let estimate = graph.estimate_route(&query, heuristic).unwrap().unwrap();
+        assert_eq!(estimate.route.distance(), 149);
+        assert_eq!(estimate.bound, Some(149));
+    }
+}
+
+#[test]
+fn test_heuristics_without_routes() {
+    let mut graph = Graph::new();
+    graph.add_connection("A", "B", 1);
+    graph.add_connection("C", "D", 1);
+    let query = Query::new(Objective::Shortest);
+    for &heuristic in &HEURISTICS {
+        assert_eq!(graph.estimate_route(&query, heuristic), Ok(None));
+    }
+    let query = Query { start: Some("E"), ..query };
+    assert!(graph.estimate_route(&query, Heuristic::TwoOpt).is_err());
+}
+
@@ -1,89 +1,333 @@
-use std::collections::{HashSet, HashMap};
+use std::collections::HashMap;
 use std::cmp;
+use std::fmt;
 use std::usize;
 use std::path::Path;
 use std::io::prelude::*;
 use std::fs::File;
 use std::str::FromStr;
+use std::env::args;
 
+mod heuristic;
+mod top;
+
+use heuristic::{Estimate, Heuristic};
+
+const UNREACHABLE: usize = usize::MAX;
+// Graphs where most distances are equal have a huge number of tied routes.
+const MAX_TIED_ROUTES: usize = 64;
+// Held-Karp takes 2^n * n^2 steps. Both objectives together need a couple of
+// seconds at 20 cities in a release build and over twice as long for each city
+// after that, so larger graphs get a heuristic unless asked otherwise.
+const EXACT_CITY_LIMIT: usize = 20;
+// The exact searches refuse larger graphs before allocating their table,
+// which takes 704 MiB at 22 cities and doubles with every city after that.
+const MAX_EXACT_CITIES: usize = 22;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Objective {
+    Shortest,
+    Longest,
+}
+
+// Cities are numbered in the order they first show up, so that sets of them
+// fit into the bits of an integer.
 struct Graph {
-    nodes: HashSet<String>,
-    connections: HashMap<(String, String), usize>,
+    names: Vec<String>,
+    indices: HashMap<String, usize>,
+    distances: Vec<Vec<Option<usize>>>,
+}
+
+// Which routes count: optionally pinned to a start and/or an end city, or
+// closed tours that come back to where they started.
+#[derive(Debug, Clone)]
+struct Query<'a> {
+    objective: Objective,
+    start: Option<&'a str>,
+    end: Option<&'a str>,
+    round_trip: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Route {
+    cities: Vec<String>,
+    legs: Vec<usize>,
+}
+
+// The optimal routes, cut off at MAX_TIED_ROUTES if there are more.
+#[derive(Debug, PartialEq)]
+struct Ties {
+    routes: Vec<Route>,
+    capped: bool,
+}
+
+impl Objective {
+    fn pick(self, a: usize, b: usize) -> usize {
+        match self {
+            Objective::Shortest => cmp::min(a, b),
+            Objective::Longest => cmp::max(a, b),
+        }
+    }
+}
+
+impl<'a> Query<'a> {
+    fn new(objective: Objective) -> Self {
+        Query {
+            objective,
+            start: None,
+            end: None,
+            round_trip: false,
+        }
+    }
+}
+
+impl Route {
+    fn distance(&self) -> usize {
+        self.legs.iter().sum()
+    }
+}
+
+impl fmt::Display for Route {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} = {}", self.cities.join(" -> "), self.distance())
+    }
 }
 
 impl Graph {
     fn new() -> Self {
         Graph {
-            nodes: HashSet::new(),
-            connections: HashMap::new(),
+            names: Vec::new(),
+            indices: HashMap::new(),
+            distances: Vec::new(),
+        }
+    }
+
+    fn get_index(&mut self, city: &str) -> usize {
+        if let Some(&index) = self.indices.get(city) {
+            return index;
+        }
+        let index = self.names.len();
+        self.names.push(city.to_owned());
+        self.indices.insert(city.to_owned(), index);
+        for row in &mut self.distances {
+            row.push(None);
         }
+        self.distances.push(vec![None; index + 1]);
+        index
+    }
+
+    fn add_directed_connection(&mut self, from: &str, to: &str, distance: usize) {
+        let from = self.get_index(from);
+        let to = self.get_index(to);
+        self.distances[from][to].get_or_insert(distance);
     }
 
     fn add_connection(&mut self, a: &str, b: &str, distance: usize) {
-        self.connections.entry((a.to_owned(), b.to_owned())).or_insert(distance);
-        self.nodes.insert(a.to_owned());
-        self.nodes.insert(b.to_owned());
-    }
-
-    fn find_route_inner(&self,
-                        city: &str,
-                        stack: &mut Vec<String>,
-                        cmp: &Fn(usize, usize) -> usize)
-                        -> Option<usize> {
-        stack.push(city.to_owned());
-        let connections = self.connections
-                              .iter()
-                              .filter_map(|c| {
-                                  match c {
-                                      (&(ref a, ref b), &distance) if a == city => {
-                                          Some((b, distance))
-                                      }
-                                      (&(ref a, ref b), &distance) if b == city => {
-                                          Some((a, distance))
-                                      }
-                                      _ => None,
-                                  }
-                              })
-                              .filter(|&(b, _)| !stack.contains(b))
-                              .collect::<Vec<_>>();
-        let optimal_distance = if connections.len() == 0 {
-            Some(0)
-        } else {
-            connections.iter().fold(None, |optimal, &(b, distance)| {
-                self.find_route_inner(&b, stack, cmp).map(|d| d + distance).map(|d| {
-                    match optimal {
-                        Some(optimal_value) => cmp(optimal_value, d),
-                        None => d,
+        self.add_directed_connection(a, b, distance);
+        self.add_directed_connection(b, a, distance);
+    }
+
+    // Held-Karp: the best distance of a path through a set of cities that
+    // ends in a given city only depends on the best paths through the set
+    // without that city, so every (set, city) pair is solved exactly once.
+    // The table is indexed by set * count + city.
+    fn held_karp(&self, objective: Objective, start: Option<usize>) -> Vec<usize> {
+        let count = self.names.len();
+        let all = (1 << count) - 1;
+        let mut best = vec![UNREACHABLE; (all + 1) * count];
+        for city in (0..count).filter(|&city| start.is_none_or(|start| start == city)) {
+            best[(1 << city) * count + city] = 0;
+        }
+
+        for visited in 1..all {
+            for last in 0..count {
+                let distance = best[visited * count + last];
+                if distance == UNREACHABLE {
+                    continue;
+                }
+                for next in (0..count).filter(|&next| visited & 1 << next == 0) {
+                    if let Some(leg) = self.distances[last][next] {
+                        let entry = &mut best[(visited | 1 << next) * count + next];
+                        *entry = if *entry == UNREACHABLE {
+                            distance + leg
+                        } else {
+                            objective.pick(*entry, distance + leg)
+                        };
                     }
-                })
-            })
-        };
-        stack.pop();
-        optimal_distance
-    }
-
-    fn find_route(&self, cmp: &Fn(usize, usize) -> usize) -> Option<usize> {
-        let mut stack = Vec::new();
-        self.nodes
-            .iter()
-            .fold(None, |optimal, a| {
-                self.find_route_inner(a, &mut stack, cmp).map(|d| {
-                    match optimal {
-                        Some(optimal_value) => cmp(optimal_value, d),
-                        None => d,
+                }
+            }
+        }
+
+        best
+    }
+
+    // Walks the table backwards from the end of a route. Every prefix of an
+    // optimal route is optimal itself, so following each predecessor that
+    // adds up to the same distance finds all tied routes. A route and its
+    // reverse only count once, in the smaller of the two directions, unless
+    // the reverse uses different directed connections. One more route than
+    // MAX_TIED_ROUTES is collected to tell whether the cap cut any off.
+    fn backtrack(&self,
+                 best: &[usize],
+                 visited: usize,
+                 round_trip: bool,
+                 path: &mut Vec<usize>,
+                 paths: &mut Vec<Vec<usize>>) {
+        let count = self.names.len();
+        let city = *path.last().unwrap();
+        let rest = visited & !(1 << city);
+        if rest == 0 {
+            let mut found = path.iter().rev().cloned().collect::<Vec<_>>();
+            if round_trip {
+                let start = found[0];
+                found.push(start);
+            }
+            let reversed = found.iter().rev().cloned().collect::<Vec<_>>();
+            match paths.iter().position(|path| *path == reversed) {
+                Some(index) if self.is_reversible(&found) => {
+                    if found < reversed {
+                        paths[index] = found;
                     }
+                }
+                _ => paths.push(found),
+            }
+            return;
+        }
+
+        let distance = best[visited * count + city];
+        for previous in (0..count).filter(|&previous| rest & 1 << previous != 0) {
+            if paths.len() > MAX_TIED_ROUTES {
+                return;
+            }
+            let before = best[rest * count + previous];
+            if before == UNREACHABLE {
+                continue;
+            }
+            if let Some(leg) = self.distances[previous][city] {
+                if before + leg == distance {
+                    path.push(previous);
+                    self.backtrack(best, rest, round_trip, path, paths);
+                    path.pop();
+                }
+            }
+        }
+    }
+
+    // Whether going the other way round is the very same trip, in which case
+    // only one of the two directions gets reported.
+    fn is_reversible(&self, path: &[usize]) -> bool {
+        path.windows(2).all(|leg| self.distances[leg[0]][leg[1]] == self.distances[leg[1]][leg[0]])
+    }
+
+    fn to_route(&self, path: &[usize]) -> Route {
+        Route {
+            cities: path.iter().map(|&city| self.names[city].clone()).collect(),
+            legs: path.windows(2).map(|leg| self.distances[leg[0]][leg[1]].unwrap()).collect(),
+        }
+    }
+
+    fn get_existing_index(&self, city: Option<&str>) -> Result<Option<usize>, String> {
+        match city {
+            Some(city) => {
+                self.indices.get(city).map(|&index| Some(index)).ok_or_else(|| {
+                    format!("Unknown city '{}'", city)
                 })
-            })
+            }
+            None => Ok(None),
+        }
+    }
+
+    // Round trips always get a start and never an end, as every city is on
+    // a tour and any of them works as its start.
+    fn get_endpoints(&self, query: &Query) -> Result<(Option<usize>, Option<usize>), String> {
+        let start = self.get_existing_index(query.start)?;
+        let end = self.get_existing_index(query.end)?;
+        if !query.round_trip {
+            return Ok((start, end));
+        }
+        if start.is_some() && end.is_some() && start != end {
+            return Err("A round trip has to end where it starts".to_owned());
+        }
+        Ok((start.or(end).or(Some(0)), None))
+    }
+
+    fn check_exact_size(&self) -> Result<(), String> {
+        if self.names.len() > MAX_EXACT_CITIES {
+            return Err(format!("The exact search handles at most {} cities, but there are {}",
+                               MAX_EXACT_CITIES,
+                               self.names.len()));
+        }
+        Ok(())
+    }
+
+    // All optimal routes, where a route and its reverse only count once
+    // unless the reverse uses different directed connections.
+    fn find_routes(&self, query: &Query) -> Result<Ties, String> {
+        let (start, end) = self.get_endpoints(query)?;
+        self.check_exact_size()?;
+        let count = self.names.len();
+        let none = Ties {
+            routes: Vec::new(),
+            capped: false,
+        };
+        if count == 0 {
+            return Ok(none);
+        }
+
+        let objective = query.objective;
+        let best = self.held_karp(objective, start);
+        let all = (1 << count) - 1;
+        let finish = |last: usize| {
+            let distance = best[all * count + last];
+            if distance == UNREACHABLE || end.is_some_and(|end| end != last) {
+                return None;
+            }
+            match start {
+                Some(start) if query.round_trip && last != start => {
+                    self.distances[last][start].map(|leg| distance + leg)
+                }
+                _ => Some(distance),
+            }
+        };
+
+        let optimal = (0..count)
+                          .filter_map(&finish)
+                          .fold(None, |optimal, distance| {
+                              Some(optimal.map_or(distance,
+                                                  |optimal| objective.pick(optimal, distance)))
+                          });
+        let optimal = match optimal {
+            Some(optimal) => optimal,
+            None => return Ok(none),
+        };
+
+        let round_trip = query.round_trip && count > 1;
+        let mut paths = Vec::new();
+        for last in (0..count).filter(|&last| finish(last) == Some(optimal)) {
+            self.backtrack(&best, all, round_trip, &mut vec![last], &mut paths);
+        }
+
+        let capped = paths.len() > MAX_TIED_ROUTES;
+        paths.sort();
+        paths.truncate(MAX_TIED_ROUTES);
+        Ok(Ties {
+            routes: paths.iter().map(|path| self.to_route(path)).collect(),
+            capped,
+        })
     }
 
-    fn find_shortest_route(&self) -> Option<usize> {
-        self.find_route(&cmp::min)
+    #[cfg(test)]
+    fn find_shortest_route(&self) -> Option<Route> {
+        self.find_routes(&Query::new(Objective::Shortest)).unwrap().routes.into_iter().next()
     }
 
-    fn find_longest_route(&self) -> Option<usize> {
-        self.find_route(&cmp::max)
+    #[cfg(test)]
+    fn find_longest_route(&self) -> Option<Route> {
+        self.find_routes(&Query::new(Objective::Longest)).unwrap().routes.into_iter().next()
     }
 
+    // "A to B = 5" is a connection both ways, "A -> B = 5" only goes from A
+    // to B.
     fn parse_connections<'a, I>(&mut self, lines: I)
         where I: IntoIterator<Item = &'a String>
     {
@@ -92,7 +336,11 @@ impl Graph {
             let a = splits[0];
             let b = splits[2];
             let distance = usize::from_str(splits[4]).expect("Couldn't parse the distance");
-            self.add_connection(a, b, distance);
+            if splits[1] == "->" {
+                self.add_directed_connection(a, b, distance);
+            } else {
+                self.add_connection(a, b, distance);
+            }
         }
     }
 }
@@ -104,18 +352,119 @@ fn read_file(path: &Path) -> Vec<String> {
     input.lines().into_iter().map(|s| s.to_owned()).collect()
 }
 
+fn print_routes(label: &str, ties: &Ties) {
+    let routes = &ties.routes;
+    match routes.len() {
+        0 => println!("{} Route is non-existent", label),
+        1 => println!("{} Route is {}", label, routes[0]),
+        tied if ties.capped => {
+            println!("{} Routes (at least {} tied, showing {}):", label, tied + 1, tied);
+            for route in routes {
+                println!("    {}", route);
+            }
+        }
+        tied => {
+            println!("{} Routes ({} tied):", label, tied);
+            for route in routes {
+                println!("    {}", route);
+            }
+        }
+    }
+}
+
+fn print_estimate(label: &str, objective: Objective, estimate: Option<Estimate>) {
+    match estimate {
+        Some(estimate) => {
+            println!("{} Route is {} (heuristic)", label, estimate.route);
+            if let Some(bound) = estimate.bound {
+                let side = match objective {
+                    Objective::Shortest => "least",
+                    Objective::Longest => "most",
+                };
+                println!("    The optimum is at {} {}", side, bound);
+            }
+        }
+        None => println!("{} Route wasn't found (heuristic)", label),
+    }
+}
+
+fn print_top_routes(label: &str, routes: &[Route]) {
+    if routes.is_empty() {
+        println!("{} Route is non-existent", label);
+        return;
+    }
+    println!("{} Routes:", label);
+    for (rank, route) in routes.iter().enumerate() {
+        println!("{:>4}. {}", rank + 1, route);
+    }
+}
+
 fn main() {
+    let arguments = args().skip(1).collect::<Vec<_>>();
+    let option = |name: &str| {
+        arguments.iter()
+                 .position(|a| a == name)
+                 .and_then(|i| arguments.get(i + 1))
+                 .map(|city| city.as_str())
+    };
+    let round_trip = arguments.iter().any(|a| a == "--round-trip");
+    let seed = option("--seed").map_or(1, |s| u64::from_str(s).expect("Couldn't parse the seed"));
+    let top = option("--top").map(|k| {
+        usize::from_str(k).expect("Couldn't parse the number of routes")
+    });
+
     let input = read_file(Path::new("input.txt"));
     let mut graph = Graph::new();
     graph.parse_connections(&input);
 
-    let shortest_route = graph.find_shortest_route();
-    println!("Shortest Route is {}",
-             shortest_route.map(|x| x.to_string()).unwrap_or("non-existent".to_owned()));
+    // Only the exact search can rank routes, so --top keeps it for any size.
+    let heuristic = match option("--heuristic") {
+        None | Some("auto") if top.is_some() || graph.names.len() <= EXACT_CITY_LIMIT => None,
+        None | Some("auto") | Some("annealing") => Some(Heuristic::Annealing(seed)),
+        Some("exact") => None,
+        Some("nearest") => Some(Heuristic::NearestNeighbour),
+        Some("2-opt") => Some(Heuristic::TwoOpt),
+        Some("or-opt") => Some(Heuristic::OrOpt),
+        Some(name) => {
+            println!("Unknown heuristic '{}', expected auto, exact, nearest, 2-opt, or-opt or \
+                      annealing",
+                     name);
+            return;
+        }
+    };
+    if heuristic.is_some() && top.is_some() {
+        println!("--top needs the exact search, which --heuristic replaces");
+        return;
+    }
 
-    let longest_route = graph.find_longest_route();
-    println!("Longest Route is {}",
-             longest_route.map(|x| x.to_string()).unwrap_or("non-existent".to_owned()));
+    for &(label, objective) in &[("Shortest", Objective::Shortest),
+                                 ("Longest", Objective::Longest)] {
+        let query = Query {
+            start: option("--start"),
+            end: option("--end"),
+            round_trip,
+            ..Query::new(objective)
+        };
+        let result = match heuristic {
+            Some(heuristic) => {
+                graph.estimate_route(&query, heuristic)
+                     .map(|estimate| print_estimate(label, objective, estimate))
+            }
+            None => {
+                match top {
+                    Some(k) => {
+                        graph.find_top_routes(&query, k)
+                             .map(|routes| print_top_routes(label, &routes))
+                    }
+                    None => graph.find_routes(&query).map(|ties| print_routes(label, &ties)),
+                }
+            }
+        };
+        if let Err(err) = result {
+            println!("{}", err);
+            return;
+        }
+    }
 }
 
 #[test]
@@ -124,8 +473,11 @@ fn test_shortest() {
     graph.add_connection("London", "Dublin", 464);
     graph.add_connection("London", "Belfast", 518);
     graph.add_connection("Dublin", "Belfast", 141);
-    let shortest_route = graph.find_shortest_route();
-    assert_eq!(shortest_route, Some(605));
+    let shortest_route = graph.find_shortest_route().unwrap();
+    assert_eq!(shortest_route.distance(), 605);
+    assert_eq!(shortest_route.cities, vec!["London", "Dublin", "Belfast"]);
+    assert_eq!(shortest_route.legs, vec![464, 141]);
+    assert_eq!(shortest_route.to_string(), "London -> Dublin -> Belfast = 605");
 }
 
 #[test]
@@ -134,6 +486,166 @@ fn test_longest() {
     graph.add_connection("London", "Dublin", 464);
     graph.add_connection("London", "Belfast", 518);
     graph.add_connection("Dublin", "Belfast", 141);
-    let longest_route = graph.find_longest_route();
-    assert_eq!(longest_route, Some(982));
+    let longest_route = graph.find_longest_route().unwrap();
+    assert_eq!(longest_route.distance(), 982);
+    assert_eq!(longest_route.to_string(), "Dublin -> London -> Belfast = 982");
+}
+
+#[test]
+fn test_incomplete_graph() {
+    let mut graph = Graph::new();
+    graph.add_connection("A", "B", 1);
+    graph.add_connection("B", "C", 2);
+    graph.add_connection("C", "D", 3);
+    graph.add_connection("A", "C", 10);
+    assert_eq!(graph.find_shortest_route().map(|r| r.distance()), Some(6));
+    assert_eq!(graph.find_longest_route().map(|r| r.distance()), Some(14));
+
+    graph.add_connection("E", "F", 1);
+    assert_eq!(graph.find_shortest_route(), None);
+    assert_eq!(Graph::new().find_shortest_route(), None);
+}
+
+#[test]
+fn test_many_cities() {
+    let mut graph = Graph::new();
+    let names = (0..16).map(|i| format!("City{}", i)).collect::<Vec<_>>();
+    for a in 0..names.len() {
+        for b in a + 1..names.len() {
+            let distance = if b == a + 1 { 1 } else { 100 + (a * 7 + b * 13) % 50 };
+            graph.add_connection(&names[a], &names[b], distance);
+        }
+    }
+    assert_eq!(graph.find_shortest_route().map(|r| r.distance()), Some(15));
+}
+
+#[test]
+fn test_too_many_cities() {
+    let mut graph = Graph::new();
+    for city in 1..MAX_EXACT_CITIES + 1 {
+        graph.add_connection(&(city - 1).to_string(), &city.to_string(), 1);
+    }
+    let query = Query::new(Objective::Shortest);
+    let err = format!("The exact search handles at most {} cities, but there are {}",
+                      MAX_EXACT_CITIES,
+                      MAX_EXACT_CITIES + 1);
+    assert_eq!(graph.find_routes(&query), Err(err.clone()));
+    assert_eq!(graph.find_top_routes(&query, 1), Err(err));
+}
+
+#[test]
+fn test_tied_routes() {
+    let mut graph = Graph::new();
+    graph.add_connection("A", "B", 1);
+    graph.add_connection("B", "C", 1);
+    graph.add_connection("A", "C", 1);
+    let routes = graph.find_routes(&Query::new(Objective::Shortest))
+                      .unwrap()
+                      .routes
+                      .iter()
+                      .map(|r| r.to_string())
+                      .collect::<Vec<_>>();
+    assert_eq!(routes, vec!["A -> B -> C = 2", "A -> C -> B = 2", "B -> A -> C = 2"]);
+
+    let mut graph = Graph::new();
+    for a in 0..10 {
+        for b in a + 1..10 {
+            graph.add_connection(&a.to_string(), &b.to_string(), 1);
+        }
+    }
+    let ties = graph.find_routes(&Query::new(Objective::Longest)).unwrap();
+    assert_eq!(ties.routes.len(), MAX_TIED_ROUTES);
+    assert!(ties.capped);
+    assert!(ties.routes.iter().all(|route| {
+        let reversed = route.cities.iter().rev().cloned().collect::<Vec<_>>();
+        ties.routes.iter().all(|other| other.cities != reversed)
+    }));
+}
+
+#[cfg(test)]
+fn find_route_strings(graph: &Graph, query: &Query) -> Vec<String> {
+    graph.find_routes(query).unwrap().routes.iter().map(|r| r.to_string()).collect()
+}
+
+#[test]
+fn test_fixed_endpoints() {
+    let mut graph = Graph::new();
+    graph.add_connection("London", "Dublin", 464);
+    graph.add_connection("London", "Belfast", 518);
+    graph.add_connection("Dublin", "Belfast", 141);
+
+    let query = Query { start: Some("Dublin"), ..Query::new(Objective::Shortest) };
+    assert_eq!(find_route_strings(&graph, &query),
+               vec!["Dublin -> Belfast -> London = 659"]);
+    let query = Query { end: Some("Dublin"), ..Query::new(Objective::Longest) };
+    assert_eq!(find_route_strings(&graph, &query),
+               vec!["Belfast -> London -> Dublin = 982"]);
+    let query = Query {
+        start: Some("Belfast"),
+        end: Some("London"),
+        ..Query::new(Objective::Shortest)
+    };
+    assert_eq!(find_route_strings(&graph, &query),
+               vec!["Belfast -> Dublin -> London = 605"]);
+    let query = Query { start: Some("Paris"), ..Query::new(Objective::Shortest) };
+    assert_eq!(graph.find_routes(&query), Err("Unknown city 'Paris'".to_owned()));
+}
+
+#[test]
+fn test_round_trips() {
+    let mut graph = Graph::new();
+    graph.add_connection("London", "Dublin", 464);
+    graph.add_connection("London", "Belfast", 518);
+    graph.add_connection("Dublin", "Belfast", 141);
+
+    let query = Query { round_trip: true, ..Query::new(Objective::Shortest) };
+    assert_eq!(find_route_strings(&graph, &query),
+               vec!["London -> Dublin -> Belfast -> London = 1123"]);
+    let query = Query {
+        start: Some("Belfast"),
+        round_trip: true,
+        ..Query::new(Objective::Longest)
+    };
+    assert_eq!(find_route_strings(&graph, &query),
+               vec!["Belfast -> London -> Dublin -> Belfast = 1123"]);
+    let query = Query {
+        start: Some("Belfast"),
+        end: Some("London"),
+        round_trip: true,
+        ..Query::new(Objective::Shortest)
+    };
+    assert!(graph.find_routes(&query).is_err());
+}
+
+#[test]
+fn test_directed_connections() {
+    let mut graph = Graph::new();
+    let lines = ["A -> B = 1", "B -> C = 1", "C -> A = 1", "B -> A = 10", "C -> B = 10",
+                 "A -> C = 10"]
+                    .iter()
+                    .map(|l| l.to_string())
+                    .collect::<Vec<_>>();
+    graph.parse_connections(&lines);
+
+    assert_eq!(find_route_strings(&graph, &Query::new(Objective::Shortest)),
+               vec!["A -> B -> C = 2", "B -> C -> A = 2", "C -> A -> B = 2"]);
+    let query = Query { round_trip: true, ..Query::new(Objective::Longest) };
+    assert_eq!(find_route_strings(&graph, &query), vec!["A -> C -> B -> A = 30"]);
+    let query = Query { start: Some("C"), end: Some("B"), ..Query::new(Objective::Shortest) };
+    assert_eq!(find_route_strings(&graph, &query), vec!["C -> A -> B = 2"]);
+
+    graph.add_directed_connection("D", "A", 1);
+    let query = Query { end: Some("D"), ..Query::new(Objective::Shortest) };
+    assert!(graph.find_routes(&query).unwrap().routes.is_empty());
+}
+
+#[test]
+fn test_directed_ties() {
+    let mut graph = Graph::new();
+    graph.add_directed_connection("A", "B", 1);
+    graph.add_directed_connection("B", "C", 2);
+    graph.add_directed_connection("C", "B", 1);
+    graph.add_directed_connection("B", "A", 2);
+    assert_eq!(find_route_strings(&graph, &Query::new(Objective::Shortest)),
+               vec!["A -> B -> C = 3", "C -> B -> A = 3"]);
 }
@@ -0,0 +1,183 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashSet};
+
+use {Graph, Objective, Query, Route, UNREACHABLE};
+
+impl Graph {
+    // Held-Karp run backwards: the best distance that is still left to go for
+    // a path that has visited a set of cities and is now in a given city.
+    fn completions(&self,
+                   objective: Objective,
+                   (start, end): (Option<usize>, Option<usize>),
+                   round_trip: bool)
+                   -> Vec<usize> {
+        let count = self.names.len();
+        let all = (1 << count) - 1;
+        let mut best = vec![UNREACHABLE; (all + 1) * count];
+        for last in (0..count).filter(|&last| end.is_none_or(|end| end == last)) {
+            best[all * count + last] = match start {
+                Some(start) if round_trip && count > 1 => {
+                    self.distances[last][start].unwrap_or(UNREACHABLE)
+                }
+                _ => 0,
+            };
+        }
+
+        for visited in (1..all).rev() {
+            for last in (0..count).filter(|&last| visited & 1 << last != 0) {
+                let mut remaining = UNREACHABLE;
+                for next in (0..count).filter(|&next| visited & 1 << next == 0) {
+                    let rest = best[(visited | 1 << next) * count + next];
+                    if let Some(leg) = self.distances[last][next].filter(|_| rest != UNREACHABLE) {
+                        remaining = if remaining == UNREACHABLE {
+                            leg + rest
+                        } else {
+                            objective.pick(remaining, leg + rest)
+                        };
+                    }
+                }
+                best[visited * count + last] = remaining;
+            }
+        }
+
+        best
+    }
+
+    // Best-first search over partial routes, ranked by their distance so far
+    // plus the best possible completion. As the completions are exact, full
+    // routes come off the queue in order and partial routes without any
+    // completion are never queued at all. Equal routes come out sorted by
+    // their cities, so the first ones match find_routes.
+    pub fn find_top_routes(&self, query: &Query, k: usize) -> Result<Vec<Route>, String> {
+        let (start, end) = self.get_endpoints(query)?;
+        self.check_exact_size()?;
+        let count = self.names.len();
+        if count == 0 || k == 0 {
+            return Ok(Vec::new());
+        }
+
+        let objective = query.objective;
+        let completions = self.completions(objective, (start, end), query.round_trip);
+        let priority = |distance: usize| {
+            match objective {
+                Objective::Shortest => -(distance as i64),
+                Objective::Longest => distance as i64,
+            }
+        };
+
+        let mut queue = BinaryHeap::new();
+        for first in (0..count).filter(|&first| start.is_none_or(|start| start == first)) {
+            let remaining = completions[(1 << first) * count + first];
+            if remaining != UNREACHABLE {
+                queue.push((priority(remaining), Reverse(vec![first]), 0));
+            }
+        }
+
+        let mut routes = Vec::new();
+        let mut found = HashSet::new();
+        while let Some((_, Reverse(mut path), distance)) = queue.pop() {
+            if path.len() == count {
+                if query.round_trip && count > 1 {
+                    let start = path[0];
+                    path.push(start);
+                }
+                let reversed = path.iter().rev().cloned().collect::<Vec<_>>();
+                if found.contains(&reversed) && self.is_reversible(&path) {
+                    continue;
+                }
+                routes.push(self.to_route(&path));
+                if routes.len() == k {
+                    break;
+                }
+                found.insert(path);
+                continue;
+            }
+
+            let last = *path.last().unwrap();
+            let visited = path.iter().fold(0, |visited, &city| visited | 1 << city);
+            for next in (0..count).filter(|&next| visited & 1 << next == 0) {
+                let remaining = completions[(visited | 1 << next) * count + next];
+                if let Some(leg) = self.distances[last][next].filter(|_| remaining != UNREACHABLE) {
+                    let mut extended = path.clone();
+                    extended.push(next);
+                    queue.push((priority(distance + leg + remaining),
+                                Reverse(extended),
+                                distance + leg));
+                }
+            }
+        }
+
+        Ok(routes)
+    }
+}
+
+#[cfg(test)]
+fn all_distances(graph: &Graph, path: &mut Vec<usize>, distances: &mut Vec<usize>) {
+    let count = graph.names.len();
+    if path.len() == count {
+        let legs = path.windows(2).map(|leg| graph.distances[leg[0]][leg[1]]);
+        if let Some(distance) = legs.sum::<Option<usize>>() {
+            distances.push(distance);
+        }
+        return;
+    }
+    for city in 0..count {
+        if !path.contains(&city) {
+            path.push(city);
+            all_distances(graph, path, distances);
+            path.pop();
+        }
+    }
+}
+
+#[test]
+fn test_top_routes() {
+    let mut graph = Graph::new();
+    graph.add_connection("London", "Dublin", 464);
+    graph.add_connection("London", "Belfast", 518);
+    graph.add_connection("Dublin", "Belfast", 141);
+
+    let top = |query: &Query| {
+        graph.find_top_routes(query, 5)
+             .unwrap()
+             .iter()
+             .map(|route| route.to_string())
+             .collect::<Vec<_>>()
+    };
+    assert_eq!(top(&Query::new(Objective::Shortest)),
+               vec!["London -> Dublin -> Belfast = 605",
+                    "London -> Belfast -> Dublin = 659",
+                    "Dublin -> London -> Belfast = 982"]);
+    assert_eq!(top(&Query::new(Objective::Longest)),
+               vec!["Dublin -> London -> Belfast = 982",
+                    "London -> Belfast -> Dublin = 659",
+                    "London -> Dublin -> Belfast = 605"]);
+    assert_eq!(top(&Query { start: Some("Belfast"), ..Query::new(Objective::Shortest) }),
+               vec!["Belfast -> Dublin -> London = 605", "Belfast -> London -> Dublin = 982"]);
+    assert_eq!(top(&Query { round_trip: true, ..Query::new(Objective::Shortest) }),
+               vec!["London -> Dublin -> Belfast -> London = 1123"]);
+    assert_eq!(graph.find_top_routes(&Query::new(Objective::Shortest), 0), Ok(Vec::new()));
+}
+
+#[test]
+fn test_top_routes_in_order() {
+    let mut graph = Graph::new();
+    for a in 0..6 {
+        for b in (0..6).filter(|&b| b != a && (a + b) % 5 != 0) {
+            graph.add_directed_connection(&a.to_string(), &b.to_string(), 10 * a + b + 1);
+        }
+    }
+    let mut distances = Vec::new();
+    all_distances(&graph, &mut Vec::new(), &mut distances);
+    distances.sort();
+
+    let shortest = graph.find_top_routes(&Query::new(Objective::Shortest), 50).unwrap();
+    assert_eq!(shortest.iter().map(|route| route.distance()).collect::<Vec<_>>(),
+               &distances[..50]);
+    let longest = graph.find_top_routes(&Query::new(Objective::Longest), 1000).unwrap();
+    assert_eq!(longest.len(), distances.len());
+    assert!(longest.iter().map(|route| route.distance()).eq(distances.into_iter().rev()));
+
+    let ties = graph.find_routes(&Query::new(Objective::Shortest)).unwrap().routes;
+    assert_eq!(&shortest[..ties.len()], &ties[..]);
+}